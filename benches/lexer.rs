@@ -0,0 +1,68 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+use delphi_linter::lexer::Lexer;
+use delphi_linter::parser::Parser;
+
+/// A unit with a class of `methods` methods and their implementations
+fn generate_unit(methods: usize) -> String {
+    let mut res = String::from(
+        "unit Generated;\n\ninterface\n\nuses\n  System.SysUtils, System.Classes;\n\ntype\n  TGenerated = class(TObject)\n  private\n    FCount: Integer;\n  public\n",
+    );
+    for i in 0..methods {
+        res += &format!(
+            "    /// Adds `{i}` to the count\n    function Method{i}(const AValue: string; ACount: Integer = {i}): Boolean;\n"
+        );
+    }
+    res += "  end;\n\nimplementation\n\n";
+    for i in 0..methods {
+        res += &format!(
+            "function TGenerated.Method{i}(const AValue: string; ACount: Integer): Boolean;
+var
+  I: Integer;
+begin
+  {{ Loop over the values }}
+  Result := False;
+  for I := 0 to ACount - 1 do
+  begin
+    if (AValue <> '') and (FCount * $FF + {i}.5E2 >= I) then
+      FCount := FCount + Length('Value ''' + AValue + '''' + #13#10); // {i}
+  end;
+  Result := FCount > {i};
+end;
+
+"
+        );
+    }
+    res += "end.\n";
+    res
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+    for methods in [100, 1_000, 10_000] {
+        let source = generate_unit(methods);
+        let lines = source.lines().count();
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new("tokens", lines), &source, |b, source| {
+            b.iter(|| Lexer::new(black_box(source)).count())
+        });
+    }
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    for methods in [100, 1_000] {
+        let source = generate_unit(methods);
+        let lines = source.lines().count();
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::new("unit", lines), &source, |b, source| {
+            b.iter(|| Parser::new(black_box(source)).parse_unit())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer, bench_parser);
+criterion_main!(benches);
@@ -0,0 +1,604 @@
+use serde::Serialize;
+
+use crate::lexer::{Span, Token};
+
+/// A parsed source file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum File {
+    Unit(Unit),
+    /// `program` in a `.dpr` file
+    Program(Program),
+    /// `library` in a `.dpr` file, a program that is built as a DLL
+    Library(Program),
+    /// `package` in a `.dpk` file
+    Package(Package),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Unit {
+    pub name: String,
+    pub interface: Vec<Interface>,
+    pub implementation: Vec<Implementation>,
+    pub initialization: Vec<Statement>,
+    pub finalization: Vec<Statement>,
+}
+
+/// A `program` or `library`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Program {
+    pub name: String,
+    /// The `uses` clause, usually with the paths of the units like `Foo in 'Foo.pas'`, and the
+    /// declarations before the main block
+    pub declarations: Vec<Implementation>,
+    /// The main `begin ... end.` block, optional in libraries
+    pub body: Vec<Statement>,
+}
+
+/// A package with its `requires` and `contains` clauses
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Package {
+    pub name: String,
+    /// Names of the packages this package needs
+    pub requires: Vec<String>,
+    /// The units of the package like `Foo in 'Foo.pas'`
+    pub contains: Vec<Import>,
+}
+
+/// Everything that can be declared in the interface section
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Interface {
+    Imports(Vec<Import>),
+    Constant(Vec<Constant>),
+    Definition(Vec<TypeDeclaration>),
+    Variable(Vec<Variable>),
+    Method(Box<MethodHead>),
+}
+
+/// Everything that can be declared in the implementation section or in front of a method body
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Implementation {
+    Imports(Vec<Import>),
+    Constant(Vec<Constant>),
+    Definition(Vec<TypeDeclaration>),
+    Variable(Vec<Variable>),
+    Label(Vec<String>),
+    Method(Box<Method>),
+    Exports(Vec<Export>),
+}
+
+/// One unit of a `uses` clause, e.g. `System.SysUtils` or `Foo in 'Foo.pas'`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Import {
+    pub name: String,
+    pub path: Option<String>,
+}
+
+/// One method of an `exports` clause, e.g. `Foo(A: Integer) name 'Foo1' index 3`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Export {
+    pub name: String,
+    /// Only given to tell overloads apart
+    pub params: Option<Vec<Parameter>>,
+    /// The exported name after `name`
+    pub export_name: Option<Expression>,
+    pub index: Option<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Constant {
+    pub name: String,
+    pub typ: Option<Type>,
+    pub value: Expression,
+}
+
+/// `A, B: Integer = 0;`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Variable {
+    pub names: Vec<String>,
+    pub typ: Type,
+    /// `X: Integer absolute Y;`
+    pub absolute: Option<String>,
+    pub value: Option<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TypeDeclaration {
+    pub name: String,
+    /// `TList<T: class> = ...`
+    pub generic_params: Vec<GenericParam>,
+    pub definition: TypeDefinition,
+    /// Position of the name
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GenericParam {
+    pub name: String,
+    /// Type names or `class`, `record` and `constructor`
+    pub constraints: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TypeDefinition {
+    Class(Class),
+    /// `TFoo = class;`
+    Forward(ClassKind),
+    Alias(Type),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ClassKind {
+    Class,
+    Interface,
+    DispInterface,
+    Record,
+    /// Old style `object` types
+    Object,
+    ClassHelper,
+    RecordHelper,
+}
+
+/// Classes, interfaces, records and helpers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Class {
+    pub kind: ClassKind,
+    pub packed: bool,
+    pub is_abstract: bool,
+    pub is_sealed: bool,
+    /// The parent class followed by the implemented interfaces
+    pub ancestors: Vec<String>,
+    /// The extended type of a helper
+    pub helper_for: Option<String>,
+    /// `['{GUID}']` of an interface
+    pub guid: Option<String>,
+    pub sections: Vec<ClassSection>,
+    /// `case Tag: Integer of ...` at the end of a record
+    pub variant_part: Option<VariantPart>,
+}
+
+/// Members that share a visibility
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassSection {
+    /// `None` for the members before the first visibility keyword
+    pub visibility: Option<Visibility>,
+    pub members: Vec<ClassMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ClassMember {
+    Field(Variable),
+    /// Fields declared after `class var`
+    ClassVar(Variable),
+    Method(Box<MethodHead>),
+    Property(Box<Property>),
+    Constant(Vec<Constant>),
+    Definition(Vec<TypeDeclaration>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Visibility {
+    StrictPrivate,
+    Private,
+    StrictProtected,
+    Protected,
+    Public,
+    Published,
+    Automated,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Property {
+    pub name: String,
+    pub is_class: bool,
+    /// Parameters of an array property `Items[Index: Integer]`
+    pub params: Vec<Parameter>,
+    /// `None` when a property of the ancestor is redeclared
+    pub typ: Option<Type>,
+    pub index: Option<Expression>,
+    pub read: Option<String>,
+    pub write: Option<String>,
+    pub stored: Option<Expression>,
+    pub default: Option<Expression>,
+    pub nodefault: bool,
+    pub implements: Vec<String>,
+    /// `default;` after an array property
+    pub is_default: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VariantPart {
+    pub tag: Option<String>,
+    pub tag_type: Type,
+    pub branches: Vec<VariantBranch>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VariantBranch {
+    pub labels: Vec<Expression>,
+    pub fields: Vec<Variable>,
+    pub variant_part: Option<Box<VariantPart>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Type {
+    Named(String),
+    /// `TList<Integer>`
+    Generic {
+        name: String,
+        args: Vec<Type>,
+    },
+    /// `string[20]`
+    ShortString(Expression),
+    /// `(Red, Green = 5, Blue)`
+    Enum(Vec<EnumValue>),
+    Set(Box<Type>),
+    /// `0..9` or `'a'..'z'`
+    Subrange(Expression, Expression),
+    /// `array[0..9, TEnum] of T`, dynamic arrays have no dimensions
+    Array {
+        dimensions: Vec<Type>,
+        element: Box<Type>,
+    },
+    /// `array of const`
+    ArrayOfConst,
+    /// `^T`
+    Pointer(Box<Type>),
+    File(Option<Box<Type>>),
+    /// `class of TFoo`
+    ClassOf(String),
+    Procedure(Box<ProceduralType>),
+    /// An anonymous `record ... end` type
+    Record(Box<Class>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnumValue {
+    pub name: String,
+    pub value: Option<Expression>,
+}
+
+/// `procedure(A: Integer) of object` or `reference to function: Integer`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProceduralType {
+    pub kind: MethodKind,
+    pub params: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub of_object: bool,
+    pub is_reference: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum MethodKind {
+    Procedure,
+    Function,
+    Constructor,
+    Destructor,
+    /// `class operator` of a record
+    Operator,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum MethodDirective {
+    Abstract,
+    Virtual,
+    Dynamic,
+    Override,
+    Overload,
+    Reintroduce,
+    Static,
+    Inline,
+    Final,
+    Forward,
+    External,
+    Message,
+    /// Calling conventions, hints and other directives in lowercase
+    Other(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MethodHead {
+    pub kind: MethodKind,
+    /// `class procedure` / `class function`
+    pub is_class: bool,
+    /// Qualified with the class name in the implementation section, e.g. `TFoo.Bar`
+    pub name: String,
+    /// `function Foo<T>(A: T): T;`
+    pub generic_params: Vec<GenericParam>,
+    pub params: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub directives: Vec<MethodDirective>,
+    /// Position of the name
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ParameterModifier {
+    Var,
+    Const,
+    Out,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Parameter {
+    pub modifier: Option<ParameterModifier>,
+    pub names: Vec<String>,
+    /// Untyped `var` and `const` parameters have no type
+    pub typ: Option<Type>,
+    pub default: Option<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Method {
+    pub head: MethodHead,
+    /// Local constants, variables, types and nested methods
+    pub declarations: Vec<Implementation>,
+    /// Statements between `begin` and `end`, `None` for `forward` and `external` methods
+    pub body: Option<Vec<Statement>>,
+}
+
+/// A statement and its source range. Equality ignores the span, so the same code at two
+/// places is equal
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
+impl PartialEq for Statement {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl From<StatementKind> for Statement {
+    /// A statement without a position, e.g. for comparisons in tests
+    fn from(kind: StatementKind) -> Self {
+        Statement::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum StatementKind {
+    /// `begin ... end`
+    Compound(Vec<Statement>),
+    Assignment {
+        target: Expression,
+        value: Expression,
+    },
+    /// A procedure call or any other expression used as a statement
+    Call(Expression),
+    If {
+        condition: Expression,
+        then_branch: Option<Box<Statement>>,
+        else_branch: Option<Box<Statement>>,
+    },
+    Case {
+        selector: Expression,
+        branches: Vec<CaseBranch>,
+        else_branch: Option<Vec<Statement>>,
+    },
+    For {
+        /// `for var I := ...`
+        inline_var: bool,
+        variable: String,
+        /// `for var I: Integer := ...`
+        typ: Option<Box<Type>>,
+        start: Expression,
+        end: Expression,
+        downto: bool,
+        body: Option<Box<Statement>>,
+    },
+    ForIn {
+        inline_var: bool,
+        variable: String,
+        typ: Option<Box<Type>>,
+        collection: Expression,
+        body: Option<Box<Statement>>,
+    },
+    While {
+        condition: Expression,
+        body: Option<Box<Statement>>,
+    },
+    Repeat {
+        body: Vec<Statement>,
+        condition: Expression,
+    },
+    Try {
+        body: Vec<Statement>,
+        handler: TryHandler,
+    },
+    With {
+        objects: Vec<Expression>,
+        body: Option<Box<Statement>>,
+    },
+    Raise {
+        exception: Option<Expression>,
+        /// `raise E at Address`
+        at: Option<Expression>,
+    },
+    Exit(Option<Expression>),
+    Break,
+    Continue,
+    Goto(String),
+    Labeled {
+        label: String,
+        statement: Option<Box<Statement>>,
+    },
+    /// Inline `var X: Integer := 0;`
+    Variable {
+        names: Vec<String>,
+        typ: Option<Type>,
+        value: Option<Expression>,
+    },
+    /// Inline `const X = 0;`
+    Constant {
+        name: String,
+        typ: Option<Type>,
+        value: Expression,
+    },
+    Asm(AsmBlock),
+}
+
+/// An `asm ... end` block, the assembler is kept as tokens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AsmBlock {
+    /// The `TokenTyp::Asm` tokens between `asm` and `end`
+    pub tokens: Vec<Token<'static>>,
+    /// Position of `asm`
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CaseBranch {
+    /// Values and ranges, ranges are `Expression::Range`
+    pub labels: Vec<Expression>,
+    pub body: Option<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum TryHandler {
+    Except(ExceptBlock),
+    Finally(Vec<Statement>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExceptBlock {
+    /// `on E: Exception do ...`
+    pub handlers: Vec<ExceptionHandler>,
+    /// The statements of an except block without handlers or of the `else` branch
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExceptionHandler {
+    pub variable: Option<String>,
+    pub typ: String,
+    pub body: Option<Statement>,
+}
+
+/// An expression and its source range, equality ignores the span like for `Statement`
+#[derive(Debug, Clone, Serialize)]
+pub struct Expression {
+    pub kind: ExpressionKind,
+    pub span: Span,
+}
+
+impl Expression {
+    pub fn new(kind: ExpressionKind, span: Span) -> Self {
+        Expression { kind, span }
+    }
+}
+
+impl PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl From<ExpressionKind> for Expression {
+    /// An expression without a position, e.g. for comparisons in tests
+    fn from(kind: ExpressionKind) -> Self {
+        Expression::new(kind, Span::default())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ExpressionKind {
+    Identifier(String),
+    Number(String),
+    String(String),
+    Nil,
+    /// `[A, B..C]`
+    Set(Vec<Expression>),
+    /// `A..B` in sets and case labels
+    Range(Box<Expression>, Box<Expression>),
+    /// `(1, 2, 3)` in typed constants
+    ArrayConstant(Vec<Expression>),
+    /// `(X: 1; Y: 2)` in typed constants
+    RecordConstant(Vec<(String, Expression)>),
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expression>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    /// `Object.Name`
+    Member {
+        object: Box<Expression>,
+        name: String,
+    },
+    /// `TList<Integer>` in `TList<Integer>.Create`
+    Generic {
+        object: Box<Expression>,
+        args: Vec<Type>,
+    },
+    /// `Object[A, B]`
+    Index {
+        object: Box<Expression>,
+        indices: Vec<Expression>,
+    },
+    Call {
+        callee: Box<Expression>,
+        args: Vec<Expression>,
+    },
+    /// `Pointer^`
+    Dereference(Box<Expression>),
+    /// `inherited` or `inherited Create(A)`
+    Inherited(Option<Box<Expression>>),
+    AnonymousMethod(Box<AnonymousMethod>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum UnaryOp {
+    Not,
+    Negate,
+    Plus,
+    /// `@`
+    AddressOf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum BinaryOp {
+    Multiply,
+    Divide,
+    Div,
+    Mod,
+    And,
+    Shl,
+    Shr,
+    As,
+    Add,
+    Subtract,
+    Or,
+    Xor,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    In,
+    Is,
+}
+
+/// `procedure(A: Integer) begin ... end` used as a value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AnonymousMethod {
+    pub kind: MethodKind,
+    pub params: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub declarations: Vec<Implementation>,
+    pub body: Vec<Statement>,
+}
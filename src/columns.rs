@@ -0,0 +1,136 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// Columns per tab stop for `ColumnUnit::Display`
+pub const DEFAULT_TAB_WIDTH: usize = 8;
+
+/// How reported columns are counted. Positions of the lexer count characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnUnit {
+    #[default]
+    Char,
+    /// Bytes of the UTF-8 text
+    Utf8,
+    /// UTF-16 code units, like the Language Server Protocol
+    Utf16,
+    /// Characters with tabs reaching to the next tab stop, like an editor shows them
+    Display,
+}
+
+impl fmt::Display for ColumnUnit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ColumnUnit::Char => "char",
+            ColumnUnit::Utf8 => "utf8",
+            ColumnUnit::Utf16 => "utf16",
+            ColumnUnit::Display => "display",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for ColumnUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "char" => Ok(ColumnUnit::Char),
+            "utf8" => Ok(ColumnUnit::Utf8),
+            "utf16" => Ok(ColumnUnit::Utf16),
+            "display" => Ok(ColumnUnit::Display),
+            _ => Err(format!(
+                "Unknown column unit `{s}`, expected char, utf8, utf16 or display"
+            )),
+        }
+    }
+}
+
+/// Converts the character columns of a text to another unit
+pub struct LineIndex<'a> {
+    /// Without the line breaks `\r\n`, `\r` and `\n`
+    lines: Vec<&'a str>,
+    unit: ColumnUnit,
+    tab_width: usize,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(text: &'a str, unit: ColumnUnit, tab_width: usize) -> Self {
+        let mut lines = Vec::new();
+        let mut rest = text;
+        while let Some(i) = rest.find(['\r', '\n']) {
+            lines.push(&rest[..i]);
+            let len = if rest[i..].starts_with("\r\n") { 2 } else { 1 };
+            rest = &rest[i + len..];
+        }
+        lines.push(rest);
+
+        LineIndex {
+            lines,
+            unit,
+            tab_width: tab_width.max(1),
+        }
+    }
+
+    /// The 1-based column in `unit` of the 1-based character column `col` in `row`. Columns
+    /// after the end of the line count as one each
+    pub fn column(&self, row: usize, col: usize) -> usize {
+        if self.unit == ColumnUnit::Char {
+            return col;
+        }
+        let line = row
+            .checked_sub(1)
+            .and_then(|i| self.lines.get(i))
+            .copied()
+            .unwrap_or_default();
+
+        let mut chars = line.chars();
+        let mut res = 0;
+        for _ in 1..col {
+            res += match (chars.next(), self.unit) {
+                (None, _) => 1,
+                (Some('\t'), ColumnUnit::Display) => self.tab_width - res % self.tab_width,
+                (Some(c), ColumnUnit::Utf8) => c.len_utf8(),
+                (Some(c), ColumnUnit::Utf16) => c.len_utf16(),
+                (Some(_), _) => 1,
+            };
+        }
+        res + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::columns::*;
+
+    #[test]
+    fn units() {
+        let text = "a\r\n\tä😀x\rb";
+        let columns = |unit| {
+            let index = LineIndex::new(text, unit, 4);
+            (1..=5).map(|col| index.column(2, col)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(columns(ColumnUnit::Char), vec![1, 2, 3, 4, 5]);
+        assert_eq!(columns(ColumnUnit::Utf8), vec![1, 2, 4, 8, 9]);
+        assert_eq!(columns(ColumnUnit::Utf16), vec![1, 2, 3, 5, 6]);
+        assert_eq!(columns(ColumnUnit::Display), vec![1, 5, 6, 7, 8]);
+
+        let index = LineIndex::new(text, ColumnUnit::Utf8, 4);
+        assert_eq!(index.column(3, 2), 2);
+        assert_eq!(index.column(9, 3), 3);
+    }
+
+    #[test]
+    fn tab_stops() {
+        let index = LineIndex::new("ab\tc\t\td", ColumnUnit::Display, 4);
+
+        let columns: Vec<usize> = (1..=7).map(|col| index.column(1, col)).collect();
+
+        assert_eq!(columns, vec![1, 2, 3, 5, 6, 9, 13]);
+        assert_eq!("utf16".parse(), Ok(ColumnUnit::Utf16));
+        assert!("bytes".parse::<ColumnUnit>().is_err());
+    }
+}
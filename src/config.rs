@@ -0,0 +1,406 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::columns::ColumnUnit;
+use crate::diagnostic::Severity;
+use crate::preprocessor::Defines;
+use crate::rule::Registry;
+
+/// Name of the config file that is searched in the directory of a linted file and its parents
+pub const CONFIG_FILE: &str = "delphi_linter.toml";
+
+/// The content of a `delphi_linter.toml`:
+///
+/// ```toml
+/// root = true
+/// defines = ["MSWINDOWS", "DEBUG"]
+/// code_page = 1251
+/// columns = "utf16"
+/// tab_width = 4
+///
+/// [values]
+/// CompilerVersion = 36.0
+///
+/// # Every combination of a platform and a build is checked, e.g. `Win32 Debug`
+/// [[configurations]]
+/// Win32 = ["MSWINDOWS", "CPUX86"]
+/// Linux64 = ["LINUX", "CPUX64"]
+///
+/// [[configurations]]
+/// Debug = ["DEBUG"]
+/// Release = ["RELEASE"]
+///
+/// [rules.type-prefix]
+/// severity = "error"
+/// class = "C"
+///
+/// [rules.todo-comment]
+/// enabled = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Stops the search for configs in parent directories
+    #[serde(default)]
+    pub root: bool,
+    /// Conditional symbols for `{$IFDEF}`, replaces the symbols of parent configs
+    pub defines: Option<Vec<String>>,
+    /// Constants for `{$IF}` like `CompilerVersion`, added to the values of parent configs
+    #[serde(default)]
+    pub values: BTreeMap<String, f64>,
+    /// Dimensions of named define sets, the files are checked with the symbols of every
+    /// combination in addition to `defines`. Replaces the configurations of parent configs
+    pub configurations: Option<Vec<BTreeMap<String, Vec<String>>>>,
+    /// Code page of files without BOM that are not valid UTF-8
+    pub code_page: Option<u16>,
+    /// Unit of the reported columns
+    pub columns: Option<ColumnUnit>,
+    /// Columns per tab stop for `columns = "display"`
+    pub tab_width: Option<usize>,
+    #[serde(default)]
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct RuleConfig {
+    pub enabled: Option<bool>,
+    pub severity: Option<Severity>,
+    /// Every other key is an option of the rule
+    #[serde(flatten)]
+    pub options: Table,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|err| err.message().to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content =
+            fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Config::parse(&content).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Merges the configs of `dir` and its parents, configs closer to `dir` take precedence
+    pub fn find(dir: &Path) -> Result<Self, String> {
+        let mut configs = Vec::new();
+        for path in find_config_files(dir) {
+            let config = Config::load(&path)?;
+            let is_root = config.root;
+            configs.push(config);
+            if is_root {
+                break;
+            }
+        }
+
+        let mut res = Config::default();
+        for config in configs.into_iter().rev() {
+            res.merge(config);
+        }
+        Ok(res)
+    }
+
+    /// Overrides the settings of `self` with the ones set in `other`
+    pub fn merge(&mut self, other: Config) {
+        self.root |= other.root;
+        if other.defines.is_some() {
+            self.defines = other.defines;
+        }
+        self.values.extend(other.values);
+        if other.configurations.is_some() {
+            self.configurations = other.configurations;
+        }
+        if other.code_page.is_some() {
+            self.code_page = other.code_page;
+        }
+        if other.columns.is_some() {
+            self.columns = other.columns;
+        }
+        if other.tab_width.is_some() {
+            self.tab_width = other.tab_width;
+        }
+
+        for (id, other) in other.rules {
+            let rule = self.rules.entry(id).or_default();
+            if other.enabled.is_some() {
+                rule.enabled = other.enabled;
+            }
+            if other.severity.is_some() {
+                rule.severity = other.severity;
+            }
+            rule.options.extend(other.options);
+        }
+    }
+
+    /// Enables, disables and configures the rules of `registry`
+    pub fn apply(&self, registry: &mut Registry) -> Result<(), String> {
+        if self.defines.is_some() || !self.values.is_empty() {
+            let mut defines = match &self.defines {
+                Some(symbols) => Defines::new(symbols),
+                None => registry.defines().clone(),
+            };
+            for (name, value) in &self.values {
+                defines.set_value(name, *value);
+            }
+            registry.set_defines(defines);
+        }
+        if let Some(dimensions) = &self.configurations {
+            registry.set_configurations(combine(dimensions));
+        }
+
+        for (id, rule) in &self.rules {
+            if registry.get(id).is_none() {
+                return Err(format!("Unknown rule `{id}`"));
+            }
+            if let Some(enabled) = rule.enabled {
+                registry.set_enabled(id, enabled);
+            }
+            if let Some(severity) = rule.severity {
+                registry.set_severity(id, severity);
+            }
+            registry
+                .configure(id, &rule.options)
+                .map_err(|err| format!("Rule `{id}`: {err}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Every combination of one define set per dimension, named by their names joined with spaces
+fn combine(dimensions: &[BTreeMap<String, Vec<String>>]) -> Vec<(String, Defines)> {
+    let mut res = vec![(String::new(), Defines::default())];
+    for dimension in dimensions.iter().filter(|d| !d.is_empty()) {
+        res = res
+            .iter()
+            .flat_map(|(prefix, defines)| {
+                dimension.iter().map(move |(name, symbols)| {
+                    let mut defines = defines.clone();
+                    defines.extend(&Defines::new(symbols));
+                    let name = if prefix.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{prefix} {name}")
+                    };
+                    (name, defines)
+                })
+            })
+            .collect();
+    }
+
+    res.retain(|(name, _)| !name.is_empty());
+    res
+}
+
+/// Config files in `dir` and its parents, the closest first
+fn find_config_files(dir: &Path) -> Vec<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+pub fn unknown_option(key: &str) -> String {
+    format!("Unknown option `{key}`")
+}
+
+pub fn option_string(key: &str, value: &Value) -> Result<String, String> {
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| format!("Option `{key}` has to be a string"))
+}
+
+pub fn option_usize(key: &str, value: &Value) -> Result<usize, String> {
+    value
+        .as_integer()
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or_else(|| format!("Option `{key}` has to be a positive integer"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+
+    #[test]
+    fn parse() {
+        let config = Config::parse(
+            "[rules.type-prefix]\nseverity = \"error\"\nclass = \"C\"\n\n[rules.todo-comment]\nenabled = false\n",
+        )
+        .unwrap();
+
+        assert!(!config.root);
+        let rule = &config.rules["type-prefix"];
+        assert_eq!(rule.enabled, None);
+        assert_eq!(rule.severity, Some(Severity::Error));
+        assert_eq!(rule.options["class"].as_str(), Some("C"));
+        assert_eq!(config.rules["todo-comment"].enabled, Some(false));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Config::parse("unknown = 1").is_err());
+        assert!(Config::parse("[rules.a]\nseverity = \"fatal\"").is_err());
+        assert!(Config::parse("[rules.a]\nenabled = 1").is_err());
+        assert!(Config::parse("columns = \"bytes\"").is_err());
+    }
+
+    #[test]
+    fn merge() {
+        let mut config =
+            Config::parse("[rules.a]\nenabled = false\nseverity = \"info\"\nx = 1\ny = 2").unwrap();
+        config.merge(Config::parse("[rules.a]\nenabled = true\ny = 3\n[rules.b]\nz = 4").unwrap());
+        config.merge(Config::parse("defines = [\"A\"]").unwrap());
+        config.merge(Config::parse("defines = [\"B\"]").unwrap());
+        config.merge(Config::parse("[values]\nA = 1\nB = 2").unwrap());
+        config.merge(Config::parse("[values]\nB = 3.5").unwrap());
+        config.merge(Config::parse("[[configurations]]\nWin32 = []").unwrap());
+        config.merge(Config::parse("code_page = 1251").unwrap());
+        config.merge(Config::parse("columns = \"display\"\ntab_width = 4").unwrap());
+        config.merge(Config::parse("columns = \"utf16\"").unwrap());
+        config.merge(Config::parse("").unwrap());
+
+        let rule = &config.rules["a"];
+        assert_eq!(rule.enabled, Some(true));
+        assert_eq!(rule.severity, Some(Severity::Info));
+        assert_eq!(rule.options["x"].as_integer(), Some(1));
+        assert_eq!(rule.options["y"].as_integer(), Some(3));
+        assert_eq!(config.rules["b"].options["z"].as_integer(), Some(4));
+        assert_eq!(config.defines, Some(vec![String::from("B")]));
+        assert_eq!(config.values["A"], 1.0);
+        assert_eq!(config.values["B"], 3.5);
+        assert_eq!(config.code_page, Some(1251));
+        assert_eq!(config.columns, Some(ColumnUnit::Utf16));
+        assert_eq!(config.tab_width, Some(4));
+        assert_eq!(
+            config.configurations.unwrap()[0]["Win32"],
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn hierarchical_configs() {
+        let root =
+            std::env::temp_dir().join(format!("delphi_linter_config_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let sub = root.join("project").join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(
+            root.join(CONFIG_FILE),
+            "[rules.a]\nx = 0\n[rules.c]\nenabled = false",
+        )
+        .unwrap();
+        fs::write(
+            root.join("project").join(CONFIG_FILE),
+            "root = true\n[rules.a]\nenabled = false\nx = 1",
+        )
+        .unwrap();
+        fs::write(
+            sub.join(CONFIG_FILE),
+            "[rules.a]\nx = 2\n[rules.b]\nenabled = false",
+        )
+        .unwrap();
+
+        let config = Config::find(&sub).unwrap();
+
+        assert_eq!(config.rules["a"].enabled, Some(false));
+        assert_eq!(config.rules["a"].options["x"].as_integer(), Some(2));
+        assert_eq!(config.rules["b"].enabled, Some(false));
+        assert!(!config.rules.contains_key("c"));
+
+        let config = Config::find(&root).unwrap();
+        assert_eq!(config.rules["a"].options["x"].as_integer(), Some(0));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn apply() {
+        let mut registry = Registry::with_builtin_rules();
+        let config = Config::parse(
+            "[rules.todo-comment]\nenabled = false\n[rules.type-prefix]\nseverity = \"error\"",
+        )
+        .unwrap();
+
+        config.apply(&mut registry).unwrap();
+        Config::parse("defines = [\"debug\"]")
+            .unwrap()
+            .apply(&mut registry)
+            .unwrap();
+
+        Config::parse("[values]\nCompilerVersion = 36")
+            .unwrap()
+            .apply(&mut registry)
+            .unwrap();
+
+        assert!(!registry.is_enabled("todo-comment"));
+        assert!(registry.defines().is_defined("DEBUG"));
+        assert_eq!(registry.defines().value("compilerversion"), Some(36.0));
+        let diagnostics =
+            registry.check("unit Foo;\ninterface\ntype A = Integer;\nimplementation\nend.");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+    }
+
+    #[test]
+    fn configurations() {
+        let config = Config::parse(
+            "defines = [\"A\"]
+[[configurations]]
+Win32 = [\"MSWINDOWS\", \"CPUX86\"]
+Linux64 = [\"LINUX\", \"CPUX64\"]
+[[configurations]]
+Release = []
+Debug = [\"DEBUG\"]",
+        )
+        .unwrap();
+        let mut registry = Registry::with_builtin_rules();
+
+        config.apply(&mut registry).unwrap();
+
+        let configurations: Vec<String> = registry
+            .configurations()
+            .iter()
+            .map(|(name, defines)| format!("{name}: {}", defines.symbols().join(" ")))
+            .collect();
+        assert_eq!(
+            configurations,
+            vec![
+                "Linux64 Debug: CPUX64 DEBUG LINUX",
+                "Linux64 Release: CPUX64 LINUX",
+                "Win32 Debug: CPUX86 DEBUG MSWINDOWS",
+                "Win32 Release: CPUX86 MSWINDOWS",
+            ]
+        );
+        assert_eq!(registry.defines().symbols(), vec!["A"]);
+
+        let config = Config::parse("configurations = []").unwrap();
+        assert!(combine(config.configurations.as_ref().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn apply_errors() {
+        let mut registry = Registry::with_builtin_rules();
+
+        let config = Config::parse("[rules.unknown]\nenabled = false").unwrap();
+        assert_eq!(
+            config.apply(&mut registry).unwrap_err(),
+            "Unknown rule `unknown`"
+        );
+
+        let config = Config::parse("[rules.todo-comment]\ncolor = \"red\"").unwrap();
+        assert_eq!(
+            config.apply(&mut registry).unwrap_err(),
+            "Rule `todo-comment`: Unknown option `color`"
+        );
+
+        let config = Config::parse("[rules.method-length]\nmax_statements = -1").unwrap();
+        assert_eq!(
+            config.apply(&mut registry).unwrap_err(),
+            "Rule `method-length`: Option `max_statements` has to be a positive integer"
+        );
+    }
+}
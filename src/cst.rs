@@ -0,0 +1,366 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Lexer, Span, Symbol, Token, TokenTyp};
+use crate::parser::BLOCK_KEYWORDS;
+use crate::reader::Position;
+
+/// Whitespace, a comment or a compiler directive
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia<'a> {
+    pub typ: TokenTyp,
+    /// As written in the source, including the delimiters of comments
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// A token together with the whitespace, comments and compiler directives around it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxToken<'a> {
+    /// Trivia from the end of the previous token's line up to the token
+    pub leading: Vec<Trivia<'a>>,
+    pub token: Token<'a>,
+    /// The token as written in the source, e.g. `&begin` for the identifier `begin`
+    pub text: &'a str,
+    /// Trivia after the token up to and including the line break
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl SyntaxToken<'_> {
+    /// The source text of the token and its trivia
+    pub fn text(&self) -> String {
+        let mut res = String::new();
+        self.write(&mut res);
+        res
+    }
+
+    fn write(&self, res: &mut String) {
+        for trivia in &self.leading {
+            *res += trivia.text;
+        }
+        *res += self.text;
+        for trivia in &self.trailing {
+            *res += trivia.text;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    File,
+    /// `( ... )`
+    Parens,
+    /// `[ ... ]` or `(. ... .)`
+    Brackets,
+    /// `begin`, `case`, `try` or `asm` up to the matching `end`
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element<'a> {
+    Node(Node<'a>),
+    Token(SyntaxToken<'a>),
+}
+
+/// A group of tokens, the delimiters are the first and last child. Unclosed groups end
+/// before the token that closes an enclosing group, or at the end of the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node<'a> {
+    pub kind: NodeKind,
+    pub children: Vec<Element<'a>>,
+}
+
+impl<'a> Node<'a> {
+    /// The source text of the node including all trivia
+    pub fn text(&self) -> String {
+        let mut res = String::new();
+        for tok in self.tokens() {
+            tok.write(&mut res);
+        }
+        res
+    }
+
+    /// All tokens of the node in source order
+    pub fn tokens(&self) -> Vec<&SyntaxToken<'a>> {
+        let mut res = Vec::new();
+        for child in &self.children {
+            match child {
+                Element::Node(node) => res.extend(node.tokens()),
+                Element::Token(tok) => res.push(tok),
+            }
+        }
+        res
+    }
+
+    /// From the first to the last token without their trivia
+    pub fn span(&self) -> Span {
+        let tokens = self.tokens();
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => Span {
+                start: first.token.span.start,
+                end: last.token.span.end,
+            },
+            _ => Span::default(),
+        }
+    }
+
+    fn is_closed_by(&self, tok: &Token) -> bool {
+        match self.kind {
+            NodeKind::File => false,
+            NodeKind::Parens => tok.typ == TokenTyp::Symbol(Symbol::RightParen),
+            NodeKind::Brackets => tok.typ == TokenTyp::Symbol(Symbol::RightBracket),
+            NodeKind::Block => {
+                tok.typ == TokenTyp::Keyword && tok.content.eq_ignore_ascii_case("end")
+            }
+        }
+    }
+}
+
+/// A lossless tree of a file, writing out all tokens with their trivia gives back the source.
+/// Every conditional branch is part of the tree, the directives are trivia
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree<'a> {
+    pub root: Node<'a>,
+    /// Syntax errors of the lexer
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> SyntaxTree<'a> {
+    pub fn parse(content: &'a str) -> Self {
+        let mut lex = Lexer::new(content);
+        let mut tokens: Vec<Token> = lex.by_ref().collect();
+        tokens.push(lex.next_token());
+
+        SyntaxTree {
+            root: build_tree(attach_trivia(content, tokens)),
+            diagnostics: lex.diagnostics().to_vec(),
+        }
+    }
+
+    /// The source text, byte for byte
+    pub fn text(&self) -> String {
+        self.root.text()
+    }
+}
+
+fn is_trivia(tok: &Token) -> bool {
+    matches!(
+        tok.typ,
+        TokenTyp::Whitespace
+            | TokenTyp::Comment
+            | TokenTyp::DocComment
+            | TokenTyp::ConditionalCompilation
+    )
+}
+
+/// Trivia on the line of a token is its trailing trivia, the rest is leading trivia of the
+/// next token. The `EOF` token gets the trivia at the end of the file
+fn attach_trivia<'a>(source: &'a str, tokens: Vec<Token<'a>>) -> Vec<SyntaxToken<'a>> {
+    let mut res: Vec<SyntaxToken> = Vec::new();
+    let mut leading = Vec::new();
+    // The trivia still belongs to the line of the last token
+    let mut is_trailing = false;
+    for tok in tokens {
+        let text = &source[tok.span.start.offset..tok.span.end.offset];
+        if !is_trivia(&tok) {
+            res.push(SyntaxToken {
+                leading: std::mem::take(&mut leading),
+                token: tok,
+                text,
+                trailing: Vec::new(),
+            });
+            is_trailing = true;
+            continue;
+        }
+
+        let trivia = Trivia {
+            typ: tok.typ,
+            text,
+            span: tok.span,
+        };
+        let Some(last) = res.last_mut().filter(|_| is_trailing) else {
+            leading.push(trivia);
+            continue;
+        };
+        if trivia.typ != TokenTyp::Whitespace {
+            last.trailing.push(trivia);
+            continue;
+        }
+        match split_line(trivia) {
+            Ok((line, rest)) => {
+                last.trailing.push(line);
+                leading.extend(rest);
+                is_trailing = false;
+            }
+            Err(trivia) => last.trailing.push(trivia),
+        }
+    }
+    res
+}
+
+/// Splits whitespace after its first line break, `Err` if it has none
+fn split_line(trivia: Trivia) -> Result<(Trivia, Option<Trivia>), Trivia> {
+    let Some(i) = trivia.text.find(['\r', '\n']) else {
+        return Err(trivia);
+    };
+    let len = if trivia.text[i..].starts_with("\r\n") {
+        2
+    } else {
+        1
+    };
+    let (line, rest) = trivia.text.split_at(i + len);
+    let middle = Position {
+        offset: trivia.span.start.offset + line.len(),
+        row: trivia.span.start.row + 1,
+        col: 1,
+    };
+
+    let rest = (!rest.is_empty()).then_some(Trivia {
+        typ: TokenTyp::Whitespace,
+        text: rest,
+        span: Span {
+            start: middle,
+            end: trivia.span.end,
+        },
+    });
+    let line = Trivia {
+        typ: TokenTyp::Whitespace,
+        text: line,
+        span: Span {
+            start: trivia.span.start,
+            end: middle,
+        },
+    };
+    Ok((line, rest))
+}
+
+fn opened_kind(tok: &Token) -> Option<NodeKind> {
+    match tok.typ {
+        TokenTyp::Symbol(Symbol::LeftParen) => Some(NodeKind::Parens),
+        TokenTyp::Symbol(Symbol::LeftBracket) => Some(NodeKind::Brackets),
+        TokenTyp::Keyword if BLOCK_KEYWORDS.contains(&tok.content.to_lowercase().as_str()) => {
+            Some(NodeKind::Block)
+        }
+        _ => None,
+    }
+}
+
+fn build_tree(tokens: Vec<SyntaxToken>) -> Node {
+    let mut stack = vec![Node {
+        kind: NodeKind::File,
+        children: Vec::new(),
+    }];
+
+    for tok in tokens {
+        // A closing token also closes the unclosed groups inside its group
+        let closed = if tok.token.typ == TokenTyp::EOF {
+            Some(0)
+        } else {
+            stack.iter().rposition(|node| node.is_closed_by(&tok.token))
+        };
+        if let Some(i) = closed {
+            while stack.len() > i + 1 {
+                close(&mut stack);
+            }
+            stack[i].children.push(Element::Token(tok));
+            if i > 0 {
+                close(&mut stack);
+            }
+        } else if let Some(kind) = opened_kind(&tok.token) {
+            stack.push(Node {
+                kind,
+                children: vec![Element::Token(tok)],
+            });
+        } else {
+            stack.last_mut().unwrap().children.push(Element::Token(tok));
+        }
+    }
+
+    while stack.len() > 1 {
+        close(&mut stack);
+    }
+    stack.pop().unwrap()
+}
+
+/// Moves the innermost open node into its parent
+fn close(stack: &mut Vec<Node>) {
+    let node = stack.pop().unwrap();
+    stack.last_mut().unwrap().children.push(Element::Node(node));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cst::*;
+
+    fn kinds(node: &Node) -> Vec<String> {
+        node.children
+            .iter()
+            .map(|child| match child {
+                Element::Node(node) => format!("{:?}", node.kind),
+                Element::Token(tok) => String::from(tok.text),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let sources = [
+            "",
+            "unit Foo;\r\n\r\ninterface\r\n\r\nimplementation\r\nend.\r\n",
+            "{ header }\n// line\n(* block\n  comment *)\nx := &begin; /// doc\n",
+            "{$IFDEF A}\n  a := 1;\n{$ELSE}\n  a := 2;\n{$ENDIF}\n\n\n",
+            "s := 'Größe' + #13#10 + '''\n  text\n  ''';\t\n",
+            "begin (a[1] end ) ] { unterminated",
+            "x := 'unterminated\r\ny := $;",
+            "x := 1;\r  { classic Mac }\r\ry := ''''\r  a\r  ''';\r",
+        ];
+        for source in sources {
+            let tree = SyntaxTree::parse(source);
+            assert_eq!(tree.text(), source);
+        }
+    }
+
+    #[test]
+    fn trivia() {
+        let tree = SyntaxTree::parse("// header\nx; { a }\n\n  { b } y // c\n");
+        let tokens = tree.root.tokens();
+
+        assert_eq!(tokens[0].text(), "// header\nx");
+        assert_eq!(tokens[1].text, ";");
+        let trailing: Vec<&str> = tokens[1].trailing.iter().map(|t| t.text).collect();
+        assert_eq!(trailing, vec![" ", "{ a }", "\n"]);
+
+        let leading: Vec<&str> = tokens[2].leading.iter().map(|t| t.text).collect();
+        assert_eq!(leading, vec!["\n  ", "{ b }", " "]);
+        assert_eq!(tokens[2].leading[0].span.start.row, 3);
+        assert_eq!(tokens[2].trailing.last().unwrap().text, "\n");
+
+        assert_eq!(tokens[3].token.typ, TokenTyp::EOF);
+        assert!(tokens[3].leading.is_empty());
+    }
+
+    #[test]
+    fn nesting() {
+        let tree = SyntaxTree::parse("begin f(a[1]); end; x");
+        assert_eq!(kinds(&tree.root), vec!["Block", ";", "x", ""]);
+
+        let Element::Node(block) = &tree.root.children[0] else {
+            panic!()
+        };
+        assert_eq!(kinds(block), vec!["begin", "f", "Parens", ";", "end"]);
+        assert_eq!(block.text(), "begin f(a[1]); end");
+        assert_eq!((block.span().start.col, block.span().end.col), (1, 19));
+
+        // `end` closes the unclosed parentheses too
+        let tree = SyntaxTree::parse("case (x of end ) ]");
+        assert_eq!(kinds(&tree.root), vec!["Block", ")", "]", ""]);
+
+        // Brackets of the assembler are not grouped
+        let tree = SyntaxTree::parse("asm mov [eax], ( end");
+        let Element::Node(block) = &tree.root.children[0] else {
+            panic!()
+        };
+        assert_eq!(
+            kinds(block),
+            vec!["asm", "mov", "[", "eax", "]", ",", "(", "end"]
+        );
+    }
+}
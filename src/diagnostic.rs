@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lexer::{Span, Token};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "info" => Ok(Severity::Info),
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            _ => Err(format!(
+                "Unknown severity `{s}`, expected info, warning or error"
+            )),
+        }
+    }
+}
+
+/// Code of the errors of the lexer, the parser and the preprocessor
+pub const SYNTAX_ERROR: &str = "syntax-error";
+
+/// A problem found in a file, positions are 1-based like in `Reader`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// `syntax-error` or the id of the rule that reported it
+    pub code: String,
+    pub message: String,
+    pub row: usize,
+    pub col: usize,
+    pub end_row: usize,
+    pub end_col: usize,
+    /// Names of the define sets in which the diagnostic was found, empty if only
+    /// a single set of defines was checked
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub configurations: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &str, message: String, row: usize, col: usize) -> Self {
+        Diagnostic {
+            severity,
+            code: String::from(code),
+            message,
+            row,
+            col,
+            end_row: row,
+            end_col: col,
+            configurations: Vec::new(),
+        }
+    }
+
+    /// A diagnostic covering `span`
+    pub fn with_span(severity: Severity, code: &str, message: String, span: Span) -> Self {
+        let mut diagnostic =
+            Diagnostic::new(severity, code, message, span.start.row, span.start.col);
+        diagnostic.end_row = span.end.row;
+        diagnostic.end_col = span.end.col;
+        diagnostic
+    }
+
+    /// A syntax error covering `tok`
+    pub fn syntax_error(tok: &Token, message: String) -> Self {
+        Diagnostic::with_span(Severity::Error, SYNTAX_ERROR, message, tok.span)
+    }
+
+    /// Equal apart from the configurations
+    pub fn is_same(&self, other: &Diagnostic) -> bool {
+        (
+            self.row,
+            self.col,
+            self.end_row,
+            self.end_col,
+            self.severity,
+        ) == (
+            other.row,
+            other.col,
+            other.end_row,
+            other.end_col,
+            other.severity,
+        ) && self.code == other.code
+            && self.message == other.message
+    }
+}
+
+/// Adds the diagnostics found in the configuration `name` to `res`,
+/// diagnostics that are already in `res` only get the configuration added
+pub fn merge_configuration(res: &mut Vec<Diagnostic>, diagnostics: Vec<Diagnostic>, name: &str) {
+    for mut diagnostic in diagnostics {
+        // Equal diagnostics of the same configuration are kept apart
+        let existing = res
+            .iter_mut()
+            .find(|d| d.is_same(&diagnostic) && !d.configurations.iter().any(|c| c == name));
+        match existing {
+            Some(existing) => existing.configurations.push(String::from(name)),
+            None => {
+                diagnostic.configurations.push(String::from(name));
+                res.push(diagnostic);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::*;
+
+    #[test]
+    fn merge() {
+        let a = Diagnostic::new(Severity::Warning, "a", String::from("A"), 1, 1);
+        let b = Diagnostic::new(Severity::Warning, "b", String::from("B"), 2, 1);
+        let c = Diagnostic::new(Severity::Error, "b", String::from("B"), 2, 1);
+
+        let mut res = Vec::new();
+        merge_configuration(&mut res, vec![a.clone(), b.clone()], "Win32");
+        merge_configuration(&mut res, vec![b.clone(), c, b], "Linux64");
+
+        assert_eq!(res.len(), 4);
+        assert_eq!(res[0].configurations, vec!["Win32"]);
+        assert_eq!(res[1].configurations, vec!["Win32", "Linux64"]);
+        assert_eq!(res[2].configurations, vec!["Linux64"]);
+        assert_eq!(res[3].configurations, vec!["Linux64"]);
+        assert!(res[0].is_same(&a));
+    }
+}
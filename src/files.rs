@@ -0,0 +1,143 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files that are linted when a directory is given
+pub const SOURCE_EXTENSIONS: [&str; 4] = ["pas", "dpr", "dpk", "inc"];
+
+/// Expands files, directories and glob patterns into a sorted list without duplicates.
+/// Directories are searched recursively for source files, files given by name are always kept
+pub fn collect_files(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut res = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            collect_dir(path, &mut res)?;
+        } else if path.is_file() {
+            res.push(path.to_path_buf());
+        } else if is_glob(input) {
+            let paths =
+                glob::glob(input).map_err(|err| format!("Invalid pattern `{input}`: {err}"))?;
+            for path in paths {
+                let path = path.map_err(|err| err.to_string())?;
+                if path.is_dir() {
+                    collect_dir(&path, &mut res)?;
+                } else if is_source_file(&path) {
+                    res.push(path);
+                }
+            }
+        } else {
+            return Err(format!("`{input}` does not exist"));
+        }
+    }
+
+    res.sort();
+    res.dedup();
+    Ok(res)
+}
+
+pub fn is_source_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            SOURCE_EXTENSIONS
+                .iter()
+                .any(|e| ext.eq_ignore_ascii_case(e))
+        })
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn collect_dir(dir: &Path, res: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+
+    for entry in entries {
+        let path = entry
+            .map_err(|err| format!("{}: {err}", dir.display()))?
+            .path();
+        if path.is_dir() {
+            collect_dir(&path, res)?;
+        } else if is_source_file(&path) {
+            res.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::files::*;
+
+    /// Creates a fresh directory tree below the temp directory
+    fn create_tree(name: &str, files: &[&str]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("delphi_linter_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        root
+    }
+
+    fn relative(root: &Path, files: Vec<PathBuf>) -> Vec<String> {
+        files
+            .iter()
+            .map(|f| {
+                f.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn directories_are_searched_recursively() {
+        let root = create_tree(
+            "dirs",
+            &[
+                "a.pas",
+                "b.DPR",
+                "notes.txt",
+                "sub/c.inc",
+                "sub/deeper/d.dpk",
+                "sub/e.dfm",
+            ],
+        );
+
+        let files = collect_files(&[root.to_string_lossy().into_owned()]).unwrap();
+
+        assert_eq!(
+            relative(&root, files),
+            vec!["a.pas", "b.DPR", "sub/c.inc", "sub/deeper/d.dpk"]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn files_and_globs() {
+        let root = create_tree("globs", &["a.pas", "b.pas", "c.txt", "sub/d.pas"]);
+        let inputs = [
+            root.join("c.txt").to_string_lossy().into_owned(),
+            root.join("*.pas").to_string_lossy().into_owned(),
+            root.join("a.pas").to_string_lossy().into_owned(),
+        ];
+
+        let files = collect_files(&inputs).unwrap();
+
+        assert_eq!(relative(&root, files), vec!["a.pas", "b.pas", "c.txt"]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn missing_file() {
+        let err = collect_files(&[String::from("does/not/exist.pas")]).unwrap_err();
+
+        assert_eq!(err, "`does/not/exist.pas` does not exist");
+    }
+}
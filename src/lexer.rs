@@ -1,295 +1,548 @@
-use core::panic;
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+use crate::diagnostic::{Diagnostic, SYNTAX_ERROR, Severity};
+use crate::reader::{Position, Reader};
+
+const SYMBOL_CHARS: [char; 17] = [
+    '+', '-', '*', '/', '=', '<', '>', ':', ';', ',', '.', '(', ')', '[', ']', '^', '@',
+];
+
+/// Operators and punctuation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Symbol {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    /// `:=`
+    Assign,
+    Equal,
+    /// `<>`
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    /// `..`
+    DotDot,
+    Caret,
+    At,
+    /// `@@`, the address of a procedural variable
+    DoubleAt,
+    LeftParen,
+    RightParen,
+    /// `[` or `(.`
+    LeftBracket,
+    /// `]` or `.)`
+    RightBracket,
+    Semicolon,
+    Comma,
+    Dot,
+    Colon,
+}
+
+impl Symbol {
+    const ALL: [(&'static str, Symbol); 25] = [
+        (":=", Symbol::Assign),
+        ("<>", Symbol::NotEqual),
+        ("<=", Symbol::LessEqual),
+        (">=", Symbol::GreaterEqual),
+        ("..", Symbol::DotDot),
+        ("@@", Symbol::DoubleAt),
+        ("(.", Symbol::LeftBracket),
+        (".)", Symbol::RightBracket),
+        ("+", Symbol::Plus),
+        ("-", Symbol::Minus),
+        ("*", Symbol::Star),
+        ("/", Symbol::Slash),
+        ("=", Symbol::Equal),
+        ("<", Symbol::Less),
+        (">", Symbol::Greater),
+        ("^", Symbol::Caret),
+        ("@", Symbol::At),
+        ("(", Symbol::LeftParen),
+        (")", Symbol::RightParen),
+        ("[", Symbol::LeftBracket),
+        ("]", Symbol::RightBracket),
+        (";", Symbol::Semicolon),
+        (",", Symbol::Comma),
+        (".", Symbol::Dot),
+        (":", Symbol::Colon),
+    ];
+
+    pub fn from_text(text: &str) -> Option<Symbol> {
+        Symbol::ALL
+            .iter()
+            .find(|(t, _)| *t == text)
+            .map(|(_, symbol)| *symbol)
+    }
+
+    /// The usual spelling, `[` and `]` for brackets
+    pub fn as_str(self) -> &'static str {
+        Symbol::ALL
+            .iter()
+            .rev()
+            .find(|(_, symbol)| *symbol == self)
+            .map(|(text, _)| *text)
+            .unwrap_or_default()
+    }
+}
 
-use crate::reader::Reader;
+/// The value of a number literal
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Number {
+    Integer(u64),
+    Real(f64),
+    /// Too large for 64 bits, a diagnostic is reported for it
+    Invalid,
+}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum TokenTyp {
+    /// `// ...`, `{ ... }` or `(* ... *)`, the content is the text without the delimiters
     Comment,
-    String,
-    Number,
+    /// `/// ...`, an XML documentation comment
+    DocComment,
+    /// `'text'`, `#13#10` or both concatenated like `'a'#13#10'b'`, the content is the text as
+    /// written and the value the decoded string
+    String(String),
+    /// `123`, `1_000`, `1.5E-3`, `$FF`, `%1010` or `&777`, the content is the text as written
+    Number(Number),
+    /// An operator or punctuation, the content is the text as written
+    Symbol(Symbol),
+    /// A compiler directive like `{$IFDEF DEBUG}`, the content is the text after the `$`
     ConditionalCompilation,
-    Operator,
+    /// A reserved word like `begin` or `class`
     Keyword,
+    /// A word like `read`, `override` or `strict` that only has a special meaning in some
+    /// places and is an identifier everywhere else
+    Directive,
     Identifier,
     Whitespace,
+    /// A word, label, number, string or symbol inside an `asm ... end` block, the content is
+    /// the text as written. The assembler is not lexed as Pascal
+    Asm,
+    /// A character that can not start any token, a diagnostic is reported for it
+    Unknown,
     EOF,
 }
 
-pub struct Token {
+/// Letters of any script and `_`
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// Letters, digits and the `_`, `@` and `$` of labels like `@@loop` and hex numbers like `$FF`
+fn is_asm_word_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '@' | '$')
+}
+
+/// Case-insensitive search in sorted lowercase `words`
+fn is_word(words: &[&str], text: &str) -> bool {
+    words
+        .binary_search_by(|w| w.bytes().cmp(text.bytes().map(|b| b.to_ascii_lowercase())))
+        .is_ok()
+}
+
+/// Radix of the number prefixes `$`, `%` and `&`
+fn radix(prefix: char) -> Option<u32> {
+    match prefix {
+        '$' => Some(16),
+        '%' => Some(2),
+        '&' => Some(8),
+        _ => None,
+    }
+}
+
+/// The source range of a token, `end` is the position after its last character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Token<'a> {
     pub typ: TokenTyp,
-    pub content: String,
-    pub row: usize,
-    pub col: usize,
+    /// Borrowed from the source unless the token was made owned
+    pub content: Cow<'a, str>,
+    pub span: Span,
+}
+
+impl Token<'_> {
+    /// A copy that does not borrow the source
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            typ: self.typ,
+            content: Cow::Owned(self.content.into_owned()),
+            span: self.span,
+        }
+    }
+}
+
+/// Splits a borrowed source into tokens. As an iterator it yields every token before `EOF`
+pub struct Lexer<'a> {
+    reader: Reader<'a>,
+    diagnostics: Vec<Diagnostic>,
+    /// Between the keywords `asm` and `end`
+    is_asm: bool,
 }
 
-pub struct Lexer {
-    reader: Reader,
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let tok = self.next_token();
+        (tok.typ != TokenTyp::EOF).then_some(tok)
+    }
 }
 
-impl Lexer {
-    pub fn new(content: String) -> Self {
+impl<'a> Lexer<'a> {
+    pub fn new(content: &'a str) -> Self {
         Lexer {
             reader: Reader::new(content),
+            diagnostics: Vec::new(),
+            is_asm: false,
         }
     }
 
-    pub fn next(&mut self) -> Token {
-        self.next_token()
+    /// Errors found in the tokens returned so far
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
 
-    fn next_token(&mut self) -> Token {
+    /// The next token, `EOF` is returned again at the end of the source
+    pub fn next_token(&mut self) -> Token<'a> {
         let char = self.reader.peek();
 
         match char {
             Some(c) if c.is_whitespace() => self.process_whitespace(),
-            Some(c) if c == '\'' => self.process_stringliteral(),
-            Some(c) if c.is_numeric() => self.process_numeric(),
-            Some(c) if c == '{' || (c == '/' && self.reader.peek_nth(1).unwrap() == '/') => {
-                self.process_comment()
+            Some('{') if self.reader.peek_nth(1) == Some('$') => self.process_directive(),
+            Some('(')
+                if self.reader.peek_nth(1) == Some('*') && self.reader.peek_nth(2) == Some('$') =>
+            {
+                self.process_directive()
             }
-            Some(c) if ['+', '-', '*', '/'].contains(&c) => Token {
-                typ: TokenTyp::Operator,
-                content: self.reader.next().unwrap().to_string(),
-                row: 0,
-                col: 0,
-            },
-            Some(c) if c.is_alphabetic() => self.process_indentifier(),
-            Some(_) => todo!(),
-            None => Token {
-                typ: TokenTyp::EOF,
-                content: String::new(),
-                row: self.reader.row,
-                col: self.reader.col,
-            },
-        }
-    }
-
-    fn process_whitespace(&mut self) -> Token {
-        let mut content = String::new();
-        let row = self.reader.row;
-        let col = self.reader.col;
-
-        while let Some(c) = self.reader.next() {
-            if !c.is_whitespace() {
-                break;
+            Some('{') => self.process_comment(),
+            Some('/') if self.reader.peek_nth(1) == Some('/') => self.process_comment(),
+            Some('(') if self.reader.peek_nth(1) == Some('*') => self.process_comment(),
+            Some(_) if self.is_asm => self.process_asm(),
+            Some('\'' | '#') => self.process_stringliteral(),
+            Some(c) if c.is_ascii_digit() => self.process_numeric(),
+            Some(c)
+                if radix(c)
+                    .is_some_and(|r| self.reader.peek_nth(1).is_some_and(|c| c.is_digit(r))) =>
+            {
+                self.process_numeric()
             }
-            content.push(c);
+            Some(c) if SYMBOL_CHARS.contains(&c) => self.process_symbol(),
+            Some(c) if is_identifier_start(c) => self.process_indentifier(),
+            Some('&') if self.reader.peek_nth(1).is_some_and(is_identifier_start) => {
+                self.process_indentifier()
+            }
+            Some(_) => self.process_unknown(),
+            None => self.token(TokenTyp::EOF, self.reader.position()),
         }
-
-        return Token {
-            typ: TokenTyp::Whitespace,
-            content: content,
-            row: row,
-            col: col,
-        };
     }
 
-    fn process_string(&mut self, quote_count: usize) -> Token {
-        let mut content = String::new();
-        let row = self.reader.row;
-        let col = self.reader.col;
+    fn process_whitespace(&mut self) -> Token<'a> {
+        let start = self.reader.position();
 
-        // String only contains '
-        if quote_count % 2 == 0 {
-            if quote_count != 2 {
-                content = self.delphi_string_escaped_quotes(quote_count);
-            }
-            return Token {
-                typ: TokenTyp::String,
-                content: content,
-                row: row,
-                col: col,
-            };
+        while self.reader.peek().is_some_and(char::is_whitespace) {
+            self.reader.next();
         }
 
+        self.token(TokenTyp::Whitespace, start)
+    }
+
+    /// A quoted part of a string, the decoded characters are appended to `value`. Strings
+    /// end at the line break, an unterminated string is reported
+    fn read_quoted(&mut self, value: &mut String) {
+        let start = self.reader.position();
+
+        // Opening quote
+        self.reader.next();
+
         loop {
-            content += &self.reader.read_until('\'');
+            *value += self.reader.read_until_any(&['\'', '\r', '\n']);
+
+            if self.reader.peek() != Some('\'') {
+                self.report(self.span_from(start), String::from("Unterminated string"));
+                break;
+            }
 
+            // Every pair of quotes is an escaped quote, an odd one closes the string
             let q_count = self.reader.count_until_not('\'');
             self.reader.advance_by(q_count);
+            value.extend(std::iter::repeat_n('\'', q_count / 2));
 
-            if q_count % 2 != 0 {
+            if !q_count.is_multiple_of(2) {
                 break;
             }
         }
-
-        return Token {
-            typ: TokenTyp::String,
-            content: content,
-            row: row,
-            col: col,
-        };
     }
 
-    fn delphi_string_escaped_quotes(&self, quote_count: usize) -> String {
-        [1..(quote_count / 2) - 1].map(|_| '\'').iter().collect()
+    /// `#13` or `#$0D`, the character is appended to `value`
+    fn read_char_code(&mut self, value: &mut String) {
+        let start = self.reader.position();
+
+        // `#`
+        self.reader.next();
+        let radix = if self.reader.peek() == Some('$') {
+            self.reader.next();
+            16
+        } else {
+            10
+        };
+        let digits = self.read_digits(radix);
+
+        let code = u32::from_str_radix(&digits.replace('_', ""), radix).ok();
+        match code.and_then(char::from_u32) {
+            Some(c) => value.push(c),
+            None => {
+                value.push(char::REPLACEMENT_CHARACTER);
+                let text = self.reader.text_from(start);
+                let message = if digits.is_empty() {
+                    format!("Expected a character code after `{text}`")
+                } else {
+                    format!("Invalid character code `{text}`")
+                };
+                self.report(self.span_from(start), message);
+            }
+        }
     }
 
-    fn process_multiline_string(&mut self, quote_count: usize) -> Token {
-        let mut content = String::new();
-        let row = self.reader.row;
-        let col = self.reader.col;
+    /// A text block like
+    ///
+    /// ```text
+    /// '''
+    ///   Line 1
+    ///   Line 2
+    ///   '''
+    /// ```
+    ///
+    /// It ends at a line with the same number of quotes as the opening, the indentation of the
+    /// closing quotes is removed from every line. Line breaks are kept as written, except for
+    /// the one before the closing quotes
+    fn process_multiline_string(&mut self, quote_count: usize) -> Token<'a> {
+        let start = self.reader.position();
 
         self.reader.advance_by(quote_count);
-        // Next must be \n
-        self.reader.next();
-
-        let indent_space_count = self.reader.count_until_not(' ');
+        let delimiter = self.span_from(start);
+        self.read_line_break();
+
+        // Every line with its span and its line break
+        let mut lines: Vec<(&str, Span, &str)> = Vec::new();
+        let mut indent = None;
+        while !self.reader.is_eof() {
+            let whitespace = self.count_indentation();
+            let quotes = self.reader.count_until_not_at(whitespace, '\'');
+            if quotes == quote_count {
+                self.reader.advance_by(whitespace + quotes);
+                indent = Some(whitespace);
+                break;
+            }
 
-        loop {
-            self.reader.advance_by(indent_space_count);
+            let line_start = self.reader.position();
+            let line = self.reader.read_until_any(&['\r', '\n']);
+            let line_span = self.span_from(line_start);
+            let line_break = self.read_line_break();
+            lines.push((line, line_span, line_break));
+        }
 
-            if self.reader.peek() == Some('\'') {
-                let end_quote_count = self.reader.count_until_not('\'');
+        let Some(indent) = indent else {
+            self.report(delimiter, String::from("Unterminated text block"));
+            let value = lines
+                .into_iter()
+                .flat_map(|(line, _, line_break)| [line, line_break])
+                .collect();
+            return self.token(TokenTyp::String(value), start);
+        };
 
-                if end_quote_count == quote_count {
-                    break;
-                } else {
-                    content = self.delphi_string_escaped_quotes(end_quote_count);
-                }
+        let mut value = String::new();
+        let count = lines.len();
+        for (i, (line, line_span, line_break)) in lines.into_iter().enumerate() {
+            let whitespace = line.chars().take_while(|c| *c == ' ' || *c == '\t').count();
+            if whitespace < indent && whitespace < line.chars().count() {
+                self.report(
+                    line_span,
+                    String::from("Line of a text block is indented less than its closing quotes"),
+                );
+            }
+            value.extend(line.chars().skip(whitespace.min(indent)));
+            if i + 1 < count {
+                value += line_break;
             }
-            content += &self.reader.read_until_any(&['\n', '\'']);
-        }
-
-        // while let Some(c) = self.reader.next() {
-        //     if c == '\'' {
-        //         let current_q_count = self.string_count_quote();
-
-        //         if is_multitline && current_q_count == q_count {
-        //             self.reader.advance_by(current_q_count);
-        //             break;
-        //         } else if is_multitline {
-        //             let _ = [0..current_q_count / 2].map(|_| content.push('\''));
-        //         }
-        //     } else {
-        //         content.push(c);
-        //     }
-        // }
-
-        content.truncate(content.len() - 1);
-
-        return Token {
-            typ: TokenTyp::String,
-            content: content,
-            row: row,
-            col: col,
+        }
+
+        self.token(TokenTyp::String(value), start)
+    }
+
+    /// Spaces and tabs at the current position
+    fn count_indentation(&self) -> usize {
+        let mut res = 0;
+        while matches!(self.reader.peek_nth(res), Some(' ' | '\t')) {
+            res += 1;
+        }
+        res
+    }
+
+    /// Skips a `\n`, `\r\n` or `\r` line break and returns it
+    fn read_line_break(&mut self) -> &'a str {
+        let len = match (self.reader.peek(), self.reader.peek_nth(1)) {
+            (Some('\r'), Some('\n')) => 2,
+            (Some('\n' | '\r'), _) => 1,
+            _ => 0,
         };
+        let start = self.reader.position();
+        self.reader.advance_by(len);
+        self.reader.text_from(start)
     }
 
-    fn process_stringliteral(&mut self) -> Token {
+    /// Quoted strings and character codes without whitespace between them form one string
+    fn process_stringliteral(&mut self) -> Token<'a> {
         let quote_count = self.reader.count_until_not('\'');
 
-        if quote_count % 2 == 0 {
-            return self.process_string(quote_count);
-        } else {
+        // Text blocks start with an odd number (>= 3) of quotes followed by a line break
+        let is_line_break = matches!(self.reader.peek_nth(quote_count), Some('\r' | '\n'));
+        if quote_count >= 3 && !quote_count.is_multiple_of(2) && is_line_break {
             return self.process_multiline_string(quote_count);
         }
 
-        // self.reader.next().unwrap();
-        // let mut content = String::new();
-        // let mut is_multitline = false;
+        let start = self.reader.position();
 
-        // let row = self.reader.row;
-        // let col = self.reader.col;
+        let mut value = String::new();
+        loop {
+            match self.reader.peek() {
+                Some('\'') => self.read_quoted(&mut value),
+                Some('#') => self.read_char_code(&mut value),
+                _ => break,
+            }
+        }
 
-        // let q_count = self.string_count_quote();
+        self.token(TokenTyp::String(value), start)
+    }
 
-        // if q_count > 1 {
-        //     self.reader.advance_by(q_count);
+    fn process_numeric(&mut self) -> Token<'a> {
+        let start = self.reader.position();
 
-        //     if let Some(c) = self.reader.peek() {
-        //         if c == '\n' {
-        //             is_multitline = true;
-        //         } else {
-        //             let _ = [0..q_count / 2].map(|_| content.push('\''));
-        //         }
-        //     }
-        // }
+        let radix = self.reader.peek().and_then(radix).unwrap_or(10);
+        if radix != 10 {
+            self.reader.next();
+        }
+        self.read_digits(radix);
+
+        let mut is_real = false;
+        if radix == 10 {
+            // A digit has to follow the `.`, so `1..5` is a range and `1.ToString` a call
+            if self.reader.peek() == Some('.')
+                && self.reader.peek_nth(1).is_some_and(|c| c.is_ascii_digit())
+            {
+                is_real = true;
+                self.reader.next();
+                self.read_digits(10);
+            }
 
-        // while let Some(c) = self.reader.next() {
-        //     if c == '\'' {
-        //         let current_q_count = self.string_count_quote();
+            let sign_len = match self.reader.peek_nth(1) {
+                Some('+' | '-') => 1,
+                _ => 0,
+            };
+            if matches!(self.reader.peek(), Some('e' | 'E'))
+                && self
+                    .reader
+                    .peek_nth(1 + sign_len)
+                    .is_some_and(|c| c.is_ascii_digit())
+            {
+                is_real = true;
+                self.reader.advance_by(1 + sign_len);
+                self.read_digits(10);
+            }
+        }
 
-        //         if is_multitline && current_q_count == q_count {
-        //             self.reader.advance_by(current_q_count);
-        //             break;
-        //         } else if is_multitline {
-        //             let _ = [0..current_q_count / 2].map(|_| content.push('\''));
-        //         }
-        //     } else {
-        //         content.push(c);
-        //     }
-        // }
+        let text = self.reader.text_from(start);
+        let digits = if radix == 10 { text } else { &text[1..] };
+        let digits = if digits.contains('_') {
+            Cow::Owned(digits.replace('_', ""))
+        } else {
+            Cow::Borrowed(digits)
+        };
+        let value = if is_real {
+            digits.parse().map_or(Number::Invalid, Number::Real)
+        } else {
+            u64::from_str_radix(&digits, radix).map_or(Number::Invalid, Number::Integer)
+        };
 
+        let tok = self.token(TokenTyp::Number(value), start);
+        if value == Number::Invalid {
+            let message = format!("Number `{}` does not fit into 64 bits", tok.content);
+            self.diagnostics
+                .push(Diagnostic::syntax_error(&tok, message));
+        }
+        tok
     }
 
-    fn process_numeric(&mut self) -> Token {
-        let mut content = String::new();
+    /// Digits of `radix` and `_` separators
+    fn read_digits(&mut self, radix: u32) -> &'a str {
+        let start = self.reader.position();
+        while self
+            .reader
+            .peek()
+            .is_some_and(|c| c.is_digit(radix) || c == '_')
+        {
+            self.reader.next();
+        }
+        self.reader.text_from(start)
+    }
 
-        let row = self.reader.row;
-        let col = self.reader.col;
+    /// The longest symbol at the current position, compound symbols have two characters
+    fn process_symbol(&mut self) -> Token<'a> {
+        let start = self.reader.position();
 
-        while let Some(c) = self.reader.next() {
-            if c.is_numeric() || c == '.' {
-                content.push(c);
-            } else {
-                break;
-            }
-        }
+        // Symbol characters are ASCII
+        let rest = self.reader.rest();
+        let text = rest
+            .get(..2)
+            .filter(|text| Symbol::from_text(text).is_some())
+            .unwrap_or(&rest[..1]);
+        self.reader.advance_by(text.len());
 
-        return Token {
-            typ: TokenTyp::Number,
-            content: content,
-            row: row,
-            col: col,
-        };
+        let symbol = Symbol::from_text(text).expect("only symbol chars are passed");
+        self.token(TokenTyp::Symbol(symbol), start)
     }
 
-    fn process_indentifier(&mut self) -> Token {
-        const DELPHI_KEYWORDS: [&str; 105] = [
-            "absolute",
-            "abstract",
+    fn process_indentifier(&mut self) -> Token<'a> {
+        // Reserved words can only be used as identifiers with a leading `&`. Both lists are
+        // sorted for `is_word`
+        const RESERVED_WORDS: [&str; 64] = [
             "and",
             "array",
             "as",
             "asm",
-            "assembler",
-            "automated",
             "begin",
             "case",
-            "cdecl",
             "class",
             "const",
             "constructor",
-            "contains",
-            "default",
             "destructor",
-            "dispid",
             "dispinterface",
             "div",
             "do",
             "downto",
-            "dynamic",
             "else",
             "end",
             "except",
-            "export",
             "exports",
-            "external",
-            "far",
             "file",
-            "final",
             "finalization",
             "finally",
             "for",
-            "forward",
             "function",
             "goto",
             "if",
             "implementation",
-            "implements",
             "in",
-            "index",
             "inherited",
             "initialization",
             "inline",
@@ -297,45 +550,23 @@ impl Lexer {
             "is",
             "label",
             "library",
-            "message",
             "mod",
-            "name",
-            "near",
             "nil",
             "not",
             "object",
             "of",
-            "on",
             "or",
-            "out",
-            "overload",
-            "override",
-            "package",
             "packed",
-            "pascal",
-            "platform",
-            "private",
             "procedure",
             "program",
             "property",
-            "protected",
-            "public",
-            "published",
             "raise",
-            "read",
             "record",
-            "register",
-            "reintroduce",
             "repeat",
-            "requires",
-            "resident",
             "resourcestring",
-            "safecall",
             "set",
             "shl",
             "shr",
-            "stdcall",
-            "stored",
             "string",
             "then",
             "threadvar",
@@ -343,109 +574,395 @@ impl Lexer {
             "try",
             "type",
             "unit",
-            "unsafe",
             "until",
             "uses",
             "var",
-            "virtual",
             "while",
             "with",
-            "write",
             "xor",
         ];
-        let mut content = String::new();
-
-        let row = self.reader.row;
-        let col = self.reader.col;
+        const DIRECTIVES: [&str; 57] = [
+            "absolute",
+            "abstract",
+            "assembler",
+            "at",
+            "automated",
+            "cdecl",
+            "contains",
+            "default",
+            "delayed",
+            "deprecated",
+            "dispid",
+            "dynamic",
+            "experimental",
+            "export",
+            "external",
+            "far",
+            "final",
+            "forward",
+            "helper",
+            "implements",
+            "index",
+            "local",
+            "message",
+            "name",
+            "near",
+            "nodefault",
+            "on",
+            "operator",
+            "out",
+            "overload",
+            "override",
+            "package",
+            "pascal",
+            "platform",
+            "private",
+            "protected",
+            "public",
+            "published",
+            "read",
+            "readonly",
+            "reference",
+            "register",
+            "reintroduce",
+            "requires",
+            "resident",
+            "safecall",
+            "sealed",
+            "static",
+            "stdcall",
+            "stored",
+            "strict",
+            "unsafe",
+            "varargs",
+            "virtual",
+            "winapi",
+            "write",
+            "writeonly",
+        ];
+        let start = self.reader.position();
 
-        if let Some(c) = self.reader.next() {
-            if c.is_alphabetic() {
-                content.push(c);
-            } else {
-                panic!("process_indentifier is_alphabetic");
-            }
-        } else {
-            panic!("process_indentifier next");
+        // `&begin` is the identifier `begin`
+        let is_escaped = self.reader.peek() == Some('&');
+        if is_escaped {
+            self.reader.next();
         }
 
-        while let Some(c) = self.reader.next() {
-            if c.is_alphanumeric() {
-                content.push(c);
-            } else {
-                break;
-            }
+        let content_start = self.reader.position();
+        while self
+            .reader
+            .peek()
+            .is_some_and(|c| is_identifier_start(c) || c.is_alphanumeric())
+        {
+            self.reader.next();
         }
+        let content = self.reader.text_from(content_start);
 
-        let typ = if DELPHI_KEYWORDS.contains(&content.as_str()) {
+        let typ = if is_escaped {
+            TokenTyp::Identifier
+        } else if is_word(&RESERVED_WORDS, content) {
+            if content.eq_ignore_ascii_case("asm") {
+                self.is_asm = true;
+            }
             TokenTyp::Keyword
+        } else if is_word(&DIRECTIVES, content) {
+            TokenTyp::Directive
         } else {
             TokenTyp::Identifier
         };
 
-        return Token {
-            typ: typ,
-            content: content,
-            row: row,
-            col: col,
-        };
+        Token {
+            typ,
+            content: Cow::Borrowed(content),
+            span: self.span_from(start),
+        }
     }
 
-    fn process_comment(&mut self) -> Token {
-        let mut content = String::new();
-        let start_char = self.reader.peek().unwrap();
+    /// `// ...`, `/// ...`, `{ ... }` or `(* ... *)`. Block comments do not nest, a `{` in a
+    /// `(* ... *)` comment or a `*)` in a `{ ... }` comment is part of the comment
+    fn process_comment(&mut self) -> Token<'a> {
+        let start = self.reader.position();
+
+        let mut typ = TokenTyp::Comment;
+        let mut is_terminated = true;
+        let content = match self.reader.peek() {
+            Some('/') => {
+                let slash_count = self.reader.count_until_not('/');
+                // `////` is a plain comment, e.g. a separator line, without any of its slashes
+                if slash_count == 3 {
+                    typ = TokenTyp::DocComment;
+                }
+                self.reader.advance_by(slash_count);
+                // The line break is not part of the comment
+                self.reader.read_until_any(&['\r', '\n'])
+            }
+            Some('{') => {
+                self.reader.advance_by(1);
+                let content;
+                (content, is_terminated) = self.read_until_close("}");
+                content
+            }
+            _ => {
+                self.reader.advance_by(2);
+                let content;
+                (content, is_terminated) = self.read_until_close("*)");
+                content
+            }
+        };
 
-        let row = self.reader.row;
-        let col = self.reader.col;
+        let tok = Token {
+            typ,
+            content: Cow::Borrowed(content),
+            span: self.span_from(start),
+        };
+        if !is_terminated {
+            self.diagnostics.push(Diagnostic::syntax_error(
+                &tok,
+                String::from("Unterminated comment"),
+            ));
+        }
+        tok
+    }
 
-        match start_char {
-            '/' => {
-                self.reader.advance_by(2);
-                while let Some(c) = self.reader.next() {
-                    if c != '\n' {
-                        content.push(c);
-                    } else {
-                        break;
-                    }
+    /// A token of an `asm` block: a word like `mov`, `eax` or `@@loop`, a number like `0FFh` or
+    /// `$FF`, a string in `'` or `"`, or any other single character. Comments and compiler
+    /// directives are lexed as in Pascal, the word `end` is the keyword that ends the block
+    fn process_asm(&mut self) -> Token<'a> {
+        let start = self.reader.position();
+
+        match self.reader.peek() {
+            Some(quote @ ('\'' | '"')) => {
+                self.reader.next();
+                self.reader.read_until_any(&[quote, '\r', '\n']);
+                if self.reader.peek() == Some(quote) {
+                    self.reader.next();
+                } else {
+                    self.report(self.span_from(start), String::from("Unterminated string"));
                 }
             }
-            '{' => {
-                self.reader.advance_by(1);
-                while let Some(c) = self.reader.next() {
-                    if c != '}' {
-                        content.push(c);
-                    } else {
-                        break;
-                    }
+            Some(c) if is_asm_word_char(c) => {
+                while self.reader.peek().is_some_and(is_asm_word_char) {
+                    self.reader.next();
                 }
             }
-            _ => panic!("WTF how did we get here"),
+            _ => {
+                self.reader.next();
+            }
+        }
+
+        let mut tok = self.token(TokenTyp::Asm, start);
+        if tok.content.eq_ignore_ascii_case("end") {
+            tok.typ = TokenTyp::Keyword;
+            self.is_asm = false;
         }
+        tok
+    }
+
+    /// `{$...}` or `(*$...*)`
+    fn process_directive(&mut self) -> Token<'a> {
+        let start = self.reader.position();
 
-        return Token {
-            typ: TokenTyp::Comment,
-            content: content,
-            row: row,
-            col: col,
+        let is_brace = self.reader.peek() == Some('{');
+        self.reader.advance_by(if is_brace { 2 } else { 3 });
+        let (content, is_terminated) = self.read_until_close(if is_brace { "}" } else { "*)" });
+
+        let tok = Token {
+            typ: TokenTyp::ConditionalCompilation,
+            content: Cow::Borrowed(content),
+            span: self.span_from(start),
         };
+        if !is_terminated {
+            self.diagnostics.push(Diagnostic::syntax_error(
+                &tok,
+                String::from("Unterminated compiler directive"),
+            ));
+        }
+        tok
+    }
+
+    /// The text up to `close`, which is skipped. `false` if the source ends before `close`
+    fn read_until_close(&mut self, close: &str) -> (&'a str, bool) {
+        let content = self.reader.read_until_str(close);
+        let is_terminated = !self.reader.is_eof();
+        self.reader.advance_by(close.len());
+        (content, is_terminated)
+    }
+
+    /// A token of the text from `start` up to the current position
+    fn token(&self, typ: TokenTyp, start: Position) -> Token<'a> {
+        Token {
+            typ,
+            content: Cow::Borrowed(self.reader.text_from(start)),
+            span: self.span_from(start),
+        }
+    }
+
+    /// The span from `start` up to the current position
+    fn span_from(&self, start: Position) -> Span {
+        Span {
+            start,
+            end: self.reader.position(),
+        }
+    }
+
+    /// A syntax error covering `span`
+    fn report(&mut self, span: Span, message: String) {
+        self.diagnostics.push(Diagnostic::with_span(
+            Severity::Error,
+            SYNTAX_ERROR,
+            message,
+            span,
+        ));
+    }
+
+    fn process_unknown(&mut self) -> Token<'a> {
+        let start = self.reader.position();
+        self.reader.next();
+
+        let tok = self.token(TokenTyp::Unknown, start);
+        self.diagnostics.push(Diagnostic::syntax_error(
+            &tok,
+            format!("Unexpected character `{}`", tok.content),
+        ));
+        tok
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lexer::{Lexer, TokenTyp};
+    use std::borrow::Cow;
+
+    use crate::lexer::{Lexer, Number, Symbol, Token, TokenTyp};
+    use crate::reader::Position;
 
     #[test]
     fn eof_token() {
-        let mut lex = Lexer::new(String::from(""));
+        let mut lex = Lexer::new("");
 
         let tok = lex.next_token();
 
         assert_eq!(tok.typ, TokenTyp::EOF);
     }
 
+    #[test]
+    fn iterator() {
+        let source = String::from("x := { c } 'a''b';");
+
+        let tokens: Vec<Token> = Lexer::new(&source).collect();
+
+        let contents: Vec<&str> = tokens.iter().map(|tok| &tok.content[..]).collect();
+        assert_eq!(
+            contents,
+            vec!["x", " ", ":=", " ", " c ", " ", "'a''b'", ";"]
+        );
+        // Contents are slices of the source
+        let range = source.as_bytes().as_ptr_range();
+        for tok in &tokens {
+            assert!(matches!(tok.content, Cow::Borrowed(_)));
+            assert!(range.contains(&tok.content.as_ptr()));
+        }
+
+        let owned = tokens[0].clone().into_owned();
+        drop(tokens);
+        drop(source);
+        assert_eq!(owned.content, "x");
+    }
+
+    #[test]
+    fn asm() {
+        let mut lex = Lexer::new(
+            "asm\n@@loop: MOV EAX, 0FFh; { don't } mov [eax+$10], 'x;'\n  jnz @@loop // it's\nEnd; 'a'",
+        );
+
+        let tokens: Vec<(TokenTyp, String)> = lex
+            .by_ref()
+            .filter(|tok| tok.typ != TokenTyp::Whitespace)
+            .map(|tok| (tok.typ, tok.content.into_owned()))
+            .collect();
+
+        let asm = |text: &str| (TokenTyp::Asm, String::from(text));
+        assert_eq!(
+            tokens,
+            vec![
+                (TokenTyp::Keyword, String::from("asm")),
+                asm("@@loop"),
+                asm(":"),
+                asm("MOV"),
+                asm("EAX"),
+                asm(","),
+                asm("0FFh"),
+                asm(";"),
+                (TokenTyp::Comment, String::from(" don't ")),
+                asm("mov"),
+                asm("["),
+                asm("eax"),
+                asm("+"),
+                asm("$10"),
+                asm("]"),
+                asm(","),
+                asm("'x;'"),
+                asm("jnz"),
+                asm("@@loop"),
+                (TokenTyp::Comment, String::from(" it's")),
+                (TokenTyp::Keyword, String::from("End")),
+                (TokenTyp::Symbol(Symbol::Semicolon), String::from(";")),
+                (TokenTyp::String(String::from("a")), String::from("'a'")),
+            ]
+        );
+        assert_eq!(lex.diagnostics(), vec![]);
+
+        // `&asm` is an identifier and does not start a block
+        let typs: Vec<TokenTyp> = Lexer::new("&asm 0FFh").map(|tok| tok.typ).collect();
+        assert_eq!(typs[2], TokenTyp::Number(Number::Integer(0)));
+    }
+
+    #[test]
+    fn spans() {
+        let mut lex = Lexer::new("Größe := 'ä';\n{ a\nb }");
+        let pos = |p: Position| (p.offset, p.row, p.col);
+
+        let mut spans = Vec::new();
+        loop {
+            let tok = lex.next_token();
+            let span = (tok.content.clone(), pos(tok.span.start), pos(tok.span.end));
+            spans.push(span);
+            if tok.typ == TokenTyp::EOF {
+                break;
+            }
+        }
+
+        let spans: Vec<_> = spans.iter().map(|(c, s, e)| (&c[..], *s, *e)).collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("Größe", (0, 1, 1), (7, 1, 6)),
+                (" ", (7, 1, 6), (8, 1, 7)),
+                (":=", (8, 1, 7), (10, 1, 9)),
+                (" ", (10, 1, 9), (11, 1, 10)),
+                ("'ä'", (11, 1, 10), (15, 1, 13)),
+                (";", (15, 1, 13), (16, 1, 14)),
+                ("\n", (16, 1, 14), (17, 2, 1)),
+                (" a\nb ", (17, 2, 1), (24, 3, 4)),
+                ("", (24, 3, 4), (24, 3, 4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_diagnostics() {
+        let mut lex = Lexer::new("x { a\nbc");
+        lex.next_token();
+        lex.next_token();
+        lex.next_token();
+
+        let d = &lex.diagnostics()[0];
+        assert_eq!(d.message, "Unterminated comment");
+        assert_eq!((d.row, d.col, d.end_row, d.end_col), (1, 3, 2, 3));
+    }
+
     #[test]
     fn whitespace_tokens() {
-        let mut lex = Lexer::new(String::from(" \t\n"));
+        let mut lex = Lexer::new(" \t\n");
 
         let tok = lex.next_token();
 
@@ -455,110 +972,374 @@ mod tests {
 
     #[test]
     fn string_tokens() {
-        let mut lex = Lexer::new(String::from("'string'"));
+        let mut lex = Lexer::new("'string'");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::String);
-        assert_eq!(tok.content, "string");
+        assert_eq!(tok.typ, TokenTyp::String(String::from("string")));
+        assert_eq!(tok.content, "'string'");
     }
 
     #[test]
     fn empty_string() {
-        let mut lex = Lexer::new(String::from("''"));
+        let mut lex = Lexer::new("''");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::String);
-        assert_eq!(tok.content, "");
+        assert_eq!(tok.typ, TokenTyp::String(String::from("")));
 
-        let mut lex = Lexer::new(String::from("''''"));
+        let mut lex = Lexer::new("''''");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::String);
-        assert_eq!(tok.content, "'");
+        assert_eq!(tok.typ, TokenTyp::String(String::from("'")));
     }
 
     #[test]
     fn multiline_string_tokens() {
-        let mut lex = Lexer::new(String::from("'''\ncool\n'''"));
+        let mut lex = Lexer::new("'''\ncool\n'''");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::String);
-        assert_eq!(tok.content, "cool");
+        assert_eq!(tok.typ, TokenTyp::String(String::from("cool")));
     }
 
     #[test]
     fn multiline_string_embeding_tokens() {
-        let mut lex = Lexer::new(String::from("'''''\nco'''ol\n'''''"));
+        let mut lex = Lexer::new("'''''\nco'''ol\n'''''");
 
         let tok = lex.next_token();
 
         println!("{}", tok.content);
 
-        assert_eq!(tok.typ, TokenTyp::String);
-        assert_eq!(tok.content, "co'''ol");
+        assert_eq!(tok.typ, TokenTyp::String(String::from("co'''ol")));
     }
 
+    #[test]
+    fn control_strings() {
+        let cases = [
+            ("#13#10", "\r\n"),
+            ("#$0A", "\n"),
+            ("#$1F600", "\u{1F600}"),
+            ("'Line1'#13#10'Line2'", "Line1\r\nLine2"),
+            ("#39'quoted'''#39", "'quoted''"),
+        ];
+
+        for (content, value) in cases {
+            let mut lex = Lexer::new(content);
+
+            let tok = lex.next_token();
+
+            assert_eq!(tok.typ, TokenTyp::String(String::from(value)), "{content}");
+            assert_eq!(tok.content, content);
+            assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+            assert!(lex.diagnostics().is_empty(), "{content}");
+        }
+
+        // Whitespace ends the string
+        let mut lex = Lexer::new("'a' #10");
+        assert_eq!(lex.next_token().content, "'a'");
+    }
+
+    #[test]
+    fn invalid_char_codes() {
+        let mut lex = Lexer::new("'a'#$D800#1114112#x");
+
+        let tok = lex.next_token();
+
+        assert_eq!(
+            tok.typ,
+            TokenTyp::String(String::from("a\u{FFFD}\u{FFFD}\u{FFFD}"))
+        );
+        assert_eq!(tok.content, "'a'#$D800#1114112#");
+        let messages: Vec<&str> = lex
+            .diagnostics()
+            .iter()
+            .map(|d| d.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid character code `#$D800`",
+                "Invalid character code `#1114112`",
+                "Expected a character code after `#`",
+            ]
+        );
+        assert_eq!(
+            (lex.diagnostics()[0].col, lex.diagnostics()[0].end_col),
+            (4, 10)
+        );
+    }
+
+    /// Decoded values of string literals as the Delphi compiler produces them
     #[test]
     fn string_escaped_tokens() {
-        // let mut lex = Lexer::new(String::from(" \t\n"));
+        let cases = [
+            ("''", ""),
+            ("''''", "'"),
+            ("''''''", "''"),
+            ("'a'''", "a'"),
+            ("'''a'", "'a"),
+            ("'a''b'", "a'b"),
+            ("'a''''b'", "a''b"),
+            ("'it''s'#13#10'ok'", "it's\r\nok"),
+            ("'{ no comment }'", "{ no comment }"),
+            ("'// no comment'", "// no comment"),
+            ("'\u{e4}\u{1F600}'", "\u{e4}\u{1F600}"),
+        ];
 
-        // let tok = lex.next_token();
+        for (content, value) in cases {
+            let mut lex = Lexer::new(content);
 
-        // println!("{}", tok.content);
+            let tok = lex.next_token();
 
-        // assert!(tok.typ == TokenTyp::Whitespace);
-        // assert!(tok.content == " \t\n");
-        todo!("bla")
+            assert_eq!(tok.typ, TokenTyp::String(String::from(value)), "{content}");
+            assert_eq!(tok.content, content);
+            assert_eq!(lex.next_token().typ, TokenTyp::EOF, "{content}");
+            assert!(lex.diagnostics().is_empty(), "{content}");
+        }
+    }
+
+    #[test]
+    fn text_blocks() {
+        let cases = [
+            ("'''\n  a\n    b\n  '''", "a\n  b"),
+            ("'''\r\n  a\r\n  b\r\n  '''", "a\r\nb"),
+            ("'''\na\n\n'''", "a\n"),
+            ("'''\n\n'''", ""),
+            ("'''\n'''", ""),
+            ("'''\n  a\n\n  b\n  '''", "a\n\nb"),
+            ("'''\n  it's ''quoted''\n  '''", "it's ''quoted''"),
+            ("'''''\n  '''\n  '''''", "'''"),
+        ];
+
+        for (content, value) in cases {
+            let mut lex = Lexer::new(content);
+
+            let tok = lex.next_token();
+
+            assert_eq!(
+                tok.typ,
+                TokenTyp::String(String::from(value)),
+                "{content:?}"
+            );
+            assert_eq!(tok.content, content);
+            assert_eq!(lex.next_token().typ, TokenTyp::EOF, "{content:?}");
+            assert!(lex.diagnostics().is_empty(), "{content:?}");
+        }
+
+        // Code may follow the closing quotes
+        let mut lex = Lexer::new("'''\n  a\n  ''';");
+        assert_eq!(lex.next_token().typ, TokenTyp::String(String::from("a")));
+        assert_eq!(lex.next_token().content, ";");
+
+        // Three quotes without a line break are an escaped quote
+        let mut lex = Lexer::new("'''a'");
+        assert_eq!(lex.next_token().typ, TokenTyp::String(String::from("'a")));
+    }
+
+    #[test]
+    fn string_errors() {
+        let mut lex = Lexer::new("'abc\r\nx");
+
+        let tok = lex.next_token();
+
+        assert_eq!(tok.typ, TokenTyp::String(String::from("abc")));
+        assert_eq!(tok.content, "'abc");
+        assert_eq!(lex.diagnostics()[0].message, "Unterminated string");
+        assert_eq!(lex.diagnostics()[0].end_col, 5);
+        assert_eq!(lex.next_token().typ, TokenTyp::Whitespace);
+        assert_eq!(lex.next_token().content, "x");
+
+        let mut lex = Lexer::new("'abc");
+        assert_eq!(lex.next_token().typ, TokenTyp::String(String::from("abc")));
+        assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+        assert_eq!(lex.diagnostics().len(), 1);
+
+        let mut lex = Lexer::new("'''\n  a\n  '");
+        assert_eq!(
+            lex.next_token().typ,
+            TokenTyp::String(String::from("  a\n  '"))
+        );
+        assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+        assert_eq!(lex.diagnostics()[0].message, "Unterminated text block");
+
+        let mut lex = Lexer::new("'''\n    a\n b\n\n    '''");
+        assert_eq!(
+            lex.next_token().typ,
+            TokenTyp::String(String::from("a\nb\n"))
+        );
+        let diagnostics = lex.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].row, 3);
+        assert_eq!(
+            diagnostics[0].message,
+            "Line of a text block is indented less than its closing quotes"
+        );
     }
 
     #[test]
     fn numeric_tokens() {
-        let mut lex = Lexer::new(String::from("12"));
+        let mut lex = Lexer::new("12");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::Number);
+        assert_eq!(tok.typ, TokenTyp::Number(Number::Integer(12)));
         assert_eq!(tok.content, "12");
 
-        let mut lex = Lexer::new(String::from("1.2"));
+        let mut lex = Lexer::new("1.2");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::Number);
+        assert_eq!(tok.typ, TokenTyp::Number(Number::Real(1.2)));
         assert_eq!(tok.content, "1.2");
     }
 
+    #[test]
+    fn numeric_literals() {
+        let cases = [
+            ("$FF", Number::Integer(255)),
+            ("$ff_ff", Number::Integer(0xFFFF)),
+            ("%1010", Number::Integer(10)),
+            ("&777", Number::Integer(0o777)),
+            ("1_000_000", Number::Integer(1_000_000)),
+            ("1.5E-3", Number::Real(1.5e-3)),
+            ("2e10", Number::Real(2e10)),
+            ("1E+2", Number::Real(100.0)),
+            ("$FFFFFFFFFFFFFFFF", Number::Integer(u64::MAX)),
+        ];
+
+        for (content, value) in cases {
+            let mut lex = Lexer::new(content);
+
+            let tok = lex.next_token();
+
+            assert_eq!(tok.typ, TokenTyp::Number(value), "{content}");
+            assert_eq!(tok.content, content);
+            assert_eq!(lex.next_token().typ, TokenTyp::EOF, "{content}");
+            assert!(lex.diagnostics().is_empty(), "{content}");
+        }
+    }
+
+    #[test]
+    fn number_boundaries() {
+        let contents = |content: &str| {
+            let mut lex = Lexer::new(content);
+            let mut res = Vec::new();
+            loop {
+                let tok = lex.next_token();
+                if tok.typ == TokenTyp::EOF {
+                    break;
+                }
+                res.push(tok.content.into_owned());
+            }
+            res
+        };
+
+        assert_eq!(contents("1..10"), vec!["1", "..", "10"]);
+        assert_eq!(contents("1.ToString"), vec!["1", ".", "ToString"]);
+        assert_eq!(contents("1e"), vec!["1", "e"]);
+        assert_eq!(contents("%12"), vec!["%1", "2"]);
+        assert_eq!(contents("&8 $G"), vec!["&", "8", " ", "$", "G"]);
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        let mut lex = Lexer::new("18446744073709551616 $1_0000_0000_0000_0000");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Number(Number::Invalid));
+        assert_eq!(
+            lex.diagnostics()[0].message,
+            "Number `18446744073709551616` does not fit into 64 bits"
+        );
+
+        lex.next_token();
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Number(Number::Invalid));
+        assert_eq!(lex.diagnostics().len(), 2);
+    }
+
     #[test]
     fn operator_tokens() {
-        let mut lex = Lexer::new(String::from("*"));
+        let mut lex = Lexer::new("*");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::Operator);
+        assert_eq!(tok.typ, TokenTyp::Symbol(Symbol::Star));
         assert_eq!(tok.content, "*");
 
-        let mut lex = Lexer::new(String::from(":"));
+        let mut lex = Lexer::new(":");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::Operator);
+        assert_eq!(tok.typ, TokenTyp::Symbol(Symbol::Colon));
         assert_eq!(tok.content, ":");
 
-        let mut lex = Lexer::new(String::from("="));
+        let mut lex = Lexer::new("=");
 
         let tok = lex.next_token();
 
-        assert_eq!(tok.typ, TokenTyp::Operator);
+        assert_eq!(tok.typ, TokenTyp::Symbol(Symbol::Equal));
         assert_eq!(tok.content, "=");
     }
 
+    #[test]
+    fn symbol_tokens() {
+        let mut lex = Lexer::new(":= <> <= >= .. ^ @ @@ ( ) [ ] (. .) ; , . : = < > + - * /");
+
+        let mut symbols = Vec::new();
+        loop {
+            let tok = lex.next_token();
+            match tok.typ {
+                TokenTyp::Symbol(symbol) => symbols.push((symbol, tok.content.into_owned())),
+                TokenTyp::Whitespace => {}
+                _ => break,
+            }
+        }
+
+        use Symbol::*;
+        let expected = [
+            (Assign, ":="),
+            (NotEqual, "<>"),
+            (LessEqual, "<="),
+            (GreaterEqual, ">="),
+            (DotDot, ".."),
+            (Caret, "^"),
+            (At, "@"),
+            (DoubleAt, "@@"),
+            (LeftParen, "("),
+            (RightParen, ")"),
+            (LeftBracket, "["),
+            (RightBracket, "]"),
+            (LeftBracket, "(."),
+            (RightBracket, ".)"),
+            (Semicolon, ";"),
+            (Comma, ","),
+            (Dot, "."),
+            (Colon, ":"),
+            (Equal, "="),
+            (Less, "<"),
+            (Greater, ">"),
+            (Plus, "+"),
+            (Minus, "-"),
+            (Star, "*"),
+            (Slash, "/"),
+        ];
+        let expected: Vec<(Symbol, String)> = expected
+            .iter()
+            .map(|(symbol, content)| (*symbol, String::from(*content)))
+            .collect();
+        assert_eq!(symbols, expected);
+        assert!(lex.diagnostics().is_empty());
+
+        assert_eq!(Symbol::LeftBracket.as_str(), "[");
+        assert_eq!(Symbol::Assign.as_str(), ":=");
+    }
+
     #[test]
     fn indentifier_tokens() {
-        let mut lex = Lexer::new(String::from("variable"));
+        let mut lex = Lexer::new("variable");
 
         let tok = lex.next_token();
 
@@ -566,9 +1347,61 @@ mod tests {
         assert_eq!(tok.content, "variable");
     }
 
+    #[test]
+    fn identifier_rules() {
+        let cases = [
+            ("_private", TokenTyp::Identifier, "_private"),
+            ("my_var2", TokenTyp::Identifier, "my_var2"),
+            ("Größe", TokenTyp::Identifier, "Größe"),
+            ("名前", TokenTyp::Identifier, "名前"),
+            ("&begin", TokenTyp::Identifier, "begin"),
+            ("&Type", TokenTyp::Identifier, "Type"),
+            ("Begin", TokenTyp::Keyword, "Begin"),
+            ("Name", TokenTyp::Directive, "Name"),
+            ("STRICT", TokenTyp::Directive, "STRICT"),
+            ("&override", TokenTyp::Identifier, "override"),
+            ("END", TokenTyp::Keyword, "END"),
+        ];
+
+        for (content, typ, name) in cases {
+            let mut lex = Lexer::new(content);
+
+            let tok = lex.next_token();
+
+            assert_eq!(tok.typ, typ, "{content}");
+            assert_eq!(tok.content, name);
+            assert_eq!(lex.next_token().typ, TokenTyp::EOF, "{content}");
+        }
+    }
+
+    #[test]
+    fn token_sequence() {
+        let mut lex = Lexer::new("a:=12;");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Identifier);
+        assert_eq!(tok.content, "a");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Symbol(Symbol::Assign));
+        assert_eq!(tok.content, ":=");
+        assert_eq!(tok.span.start.col, 2);
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Number(Number::Integer(12)));
+        assert_eq!(tok.content, "12");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Symbol(Symbol::Semicolon));
+        assert_eq!(tok.content, ";");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::EOF);
+    }
+
     #[test]
     fn keyword_tokens() {
-        let mut lex = Lexer::new(String::from("const"));
+        let mut lex = Lexer::new("const");
 
         let tok = lex.next_token();
 
@@ -576,20 +1409,132 @@ mod tests {
         assert_eq!(tok.content, "const");
     }
 
+    #[test]
+    fn unknown_tokens() {
+        let mut lex = Lexer::new("a ? b");
+
+        lex.next_token();
+        lex.next_token();
+        let tok = lex.next_token();
+
+        assert_eq!(tok.typ, TokenTyp::Unknown);
+        assert_eq!(tok.content, "?");
+        assert_eq!(lex.diagnostics().len(), 1);
+        assert_eq!(lex.diagnostics()[0].col, 3);
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Whitespace);
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let mut lex = Lexer::new("/");
+
+        let tok = lex.next_token();
+
+        assert_eq!(tok.typ, TokenTyp::Symbol(Symbol::Slash));
+        assert_eq!(tok.content, "/");
+        assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+    }
+
     #[test]
     fn comment_tokens() {
-        let mut lex = Lexer::new(String::from("// nice comment\n"));
+        let mut lex = Lexer::new("// nice comment\n");
 
         let tok = lex.next_token();
 
         assert_eq!(tok.typ, TokenTyp::Comment);
         assert_eq!(tok.content, " nice comment");
 
-        let mut lex = Lexer::new(String::from("{another one}"));
+        let mut lex = Lexer::new("{another one}");
 
         let tok = lex.next_token();
 
         assert_eq!(tok.typ, TokenTyp::Comment);
         assert_eq!(tok.content, "another one");
+
+        let mut lex = Lexer::new("(* old\ncode *)x");
+
+        let tok = lex.next_token();
+
+        assert_eq!(tok.typ, TokenTyp::Comment);
+        assert_eq!(tok.content, " old\ncode ");
+        assert_eq!(lex.next_token().content, "x");
+        assert!(lex.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn doc_comment_tokens() {
+        let mut lex = Lexer::new("/// <summary>Foo</summary>\n//// line\n////////\n");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::DocComment);
+        assert_eq!(tok.content, " <summary>Foo</summary>");
+        assert_eq!(lex.next_token().content, "\n");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Comment);
+        assert_eq!(tok.content, " line");
+        assert_eq!(lex.next_token().content, "\n");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Comment);
+        assert_eq!(tok.content, "");
+        assert_eq!((tok.span.start.col, tok.span.end.col), (1, 9));
+    }
+
+    #[test]
+    fn mixed_comment_styles() {
+        let mut lex = Lexer::new("(* { *)x{ (* *) }y(* } *)");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.content, " { ");
+        assert_eq!(lex.next_token().content, "x");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::Comment);
+        assert_eq!(tok.content, " (* *) ");
+        assert_eq!(lex.next_token().content, "y");
+        assert_eq!(lex.next_token().content, " } ");
+        assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+    }
+
+    #[test]
+    fn unterminated_comments() {
+        for content in ["{ open", "(* open", "(* open *", "(*"] {
+            let mut lex = Lexer::new(content);
+
+            assert_eq!(lex.next_token().typ, TokenTyp::Comment);
+            assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+            assert_eq!(lex.diagnostics().len(), 1, "{content}");
+            assert_eq!(lex.diagnostics()[0].message, "Unterminated comment");
+        }
+
+        let mut lex = Lexer::new("// open");
+        assert_eq!(lex.next_token().typ, TokenTyp::Comment);
+        assert!(lex.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn directive_tokens() {
+        let mut lex = Lexer::new("{$IFDEF DEBUG}(*$ENDIF*){ $R }(*x*)");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::ConditionalCompilation);
+        assert_eq!(tok.content, "IFDEF DEBUG");
+
+        let tok = lex.next_token();
+        assert_eq!(tok.typ, TokenTyp::ConditionalCompilation);
+        assert_eq!(tok.content, "ENDIF");
+        assert_eq!(tok.span.start.col, 15);
+
+        assert_eq!(lex.next_token().typ, TokenTyp::Comment);
+        assert_eq!(lex.next_token().content, "x");
+        assert!(lex.diagnostics().is_empty());
+
+        let mut lex = Lexer::new("{$IFDEF DEBUG");
+        assert_eq!(lex.next_token().content, "IFDEF DEBUG");
+        assert_eq!(lex.next_token().typ, TokenTyp::EOF);
+        assert_eq!(lex.diagnostics().len(), 1);
     }
 }
@@ -0,0 +1,14 @@
+pub mod ast;
+pub mod columns;
+pub mod config;
+pub mod cst;
+pub mod diagnostic;
+pub mod files;
+pub mod lexer;
+pub mod parser;
+pub mod preprocessor;
+pub mod reader;
+pub mod rule;
+pub mod rules;
+pub mod source;
+pub mod suppression;
@@ -1,7 +1,760 @@
-pub mod lexer;
-pub mod reader;
-pub mod parser;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-fn main() {
-    println!("Hello, world!");
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+
+use delphi_linter::columns::{ColumnUnit, DEFAULT_TAB_WIDTH, LineIndex};
+use delphi_linter::config::Config;
+use delphi_linter::diagnostic::{Diagnostic, Severity};
+use delphi_linter::files::collect_files;
+use delphi_linter::lexer::{Lexer, Span, Token};
+use delphi_linter::parser::Parser as DelphiParser;
+use delphi_linter::preprocessor::Defines;
+use delphi_linter::rule::Registry;
+use delphi_linter::source::{DEFAULT_CODE_PAGE, SourceFile, code_page_encoding};
+
+/// Lints Delphi source files
+#[derive(Parser)]
+#[command(version, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Files, directories or glob patterns, directories are searched recursively
+    #[arg(required_unless_present = "list_rules")]
+    paths: Vec<String>,
+
+    /// Only run these rules
+    #[arg(long, value_delimiter = ',', value_name = "RULE")]
+    rules: Vec<String>,
+
+    /// Do not run these rules
+    #[arg(long, value_delimiter = ',', value_name = "RULE")]
+    disable: Vec<String>,
+
+    /// Hide diagnostics below this severity: info, warning or error
+    #[arg(long, default_value = "info")]
+    severity: Severity,
+
+    /// Output format of the diagnostics and of the `tokens` and `ast` dumps
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Only print the diagnostics
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print every file that is checked
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Define conditional symbols for `{$IFDEF}` or constants for `{$IF}` like
+    /// `CompilerVersion=36` in addition to the ones of the config
+    #[arg(
+        short = 'D',
+        long = "define",
+        global = true,
+        value_delimiter = ',',
+        value_name = "SYMBOL[=VALUE]"
+    )]
+    defines: Vec<String>,
+
+    /// Code page of files without BOM that are not valid UTF-8, e.g. 1251 [default: 1252]
+    #[arg(long, global = true, value_name = "CODE_PAGE")]
+    code_page: Option<u16>,
+
+    /// Unit of the reported columns: char, utf8, utf16 or display [default: char]
+    #[arg(long, value_name = "UNIT")]
+    columns: Option<ColumnUnit>,
+
+    /// Columns per tab stop for `--columns display` [default: 8]
+    #[arg(long, value_name = "WIDTH")]
+    tab_width: Option<usize>,
+
+    /// Use this config instead of the `delphi_linter.toml` files next to the linted files
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Print the available rules and exit
+    #[arg(long)]
+    list_rules: bool,
+}
+
+/// Debug output for writing rules
+#[derive(Subcommand)]
+enum Command {
+    /// Print the tokens of a file, including whitespace and comments
+    Tokens { file: PathBuf },
+    /// Print the syntax tree of a unit, program, library or package
+    Ast { file: PathBuf },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    /// `file:row:col: severity [rule] message`
+    Text,
+    Json,
+}
+
+/// A diagnostic together with its file for the JSON output
+#[derive(Serialize)]
+struct FileDiagnostic<'a> {
+    file: String,
+    #[serde(flatten)]
+    diagnostic: &'a Diagnostic,
+}
+
+/// Everything that can differ between the directories of the linted files
+struct Settings {
+    registry: Registry,
+    code_page: u16,
+    columns: ColumnUnit,
+    tab_width: usize,
+}
+
+/// Exit code for diagnostics with severity error
+const EXIT_LINT_ERRORS: u8 = 1;
+/// Exit code for invalid arguments and unreadable files
+const EXIT_FAILURE: u8 = 2;
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let explicit_config = match &args.config {
+        Some(path) => match Config::load(path) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                eprintln!("error: {err}");
+                return ExitCode::from(EXIT_FAILURE);
+            }
+        },
+        None => None,
+    };
+
+    if let Some(command) = &args.command {
+        return match dump(&args, command, explicit_config.as_ref()) {
+            Ok(code) => code,
+            Err(err) => {
+                eprintln!("error: {err}");
+                ExitCode::from(EXIT_FAILURE)
+            }
+        };
+    }
+
+    let config = explicit_config.clone().unwrap_or_default();
+    let registry = match create_settings(&args, &config) {
+        Ok(settings) => settings.registry,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(EXIT_FAILURE);
+        }
+    };
+
+    if args.list_rules {
+        for rule in registry.rules() {
+            println!(
+                "{:<16} {:<8} {}",
+                rule.id(),
+                rule.default_severity(),
+                rule.description()
+            );
+        }
+        return ExitCode::SUCCESS;
+    }
+
+    let files = match collect_files(&args.paths) {
+        Ok(files) => files,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(EXIT_FAILURE);
+        }
+    };
+
+    // Files in the same directory share their configs
+    let mut settings_by_dir: HashMap<PathBuf, Settings> = HashMap::new();
+    let mut failed = false;
+    let mut results = Vec::new();
+    for path in &files {
+        let dir = config_dir(path);
+        if !settings_by_dir.contains_key(&dir) {
+            let settings = match &explicit_config {
+                Some(config) => create_settings(&args, config),
+                None => Config::find(&dir)
+                    .and_then(|config| create_settings(&args, &config))
+                    .map_err(|err| format!("Config of {}: {err}", dir.display())),
+            };
+            match settings {
+                Ok(settings) => settings_by_dir.insert(dir.clone(), settings),
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    failed = true;
+                    continue;
+                }
+            };
+        }
+        let settings = &settings_by_dir[&dir];
+
+        let file = match SourceFile::load(path, settings.code_page) {
+            Ok(file) => file,
+            Err(err) => {
+                eprintln!("error: {err}");
+                failed = true;
+                continue;
+            }
+        };
+        if args.verbose {
+            eprintln!("Checking {} ({})", path.display(), file.encoding);
+        }
+
+        let mut diagnostics = check(&settings.registry, path, &file.text);
+        convert_columns(&mut diagnostics, &file.text, settings);
+        diagnostics.retain(|d| d.severity >= args.severity);
+        results.push((path, diagnostics));
+    }
+
+    print_results(&args, &results);
+
+    let has_errors = results
+        .iter()
+        .flat_map(|(_, diagnostics)| diagnostics)
+        .any(|d| d.severity == Severity::Error);
+
+    if failed {
+        ExitCode::from(EXIT_FAILURE)
+    } else if has_errors {
+        ExitCode::from(EXIT_LINT_ERRORS)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// The directory whose configs apply to `path`
+fn config_dir(path: &Path) -> PathBuf {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+fn create_settings(args: &Args, config: &Config) -> Result<Settings, String> {
+    Ok(Settings {
+        registry: create_registry(args, config)?,
+        code_page: code_page(args, config)?,
+        columns: args.columns.or(config.columns).unwrap_or_default(),
+        tab_width: args
+            .tab_width
+            .or(config.tab_width)
+            .unwrap_or(DEFAULT_TAB_WIDTH),
+    })
+}
+
+/// The built-in rules configured by `config` and then by the command line
+fn create_registry(args: &Args, config: &Config) -> Result<Registry, String> {
+    let mut registry = Registry::with_builtin_rules();
+    config.apply(&mut registry)?;
+
+    let mut defines = registry.defines().clone();
+    for symbol in &args.defines {
+        match symbol.split_once('=') {
+            Some((name, value)) => {
+                let value = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid value of `{name}`: `{value}`"))?;
+                defines.set_value(name.trim(), value);
+            }
+            None => defines.define(symbol),
+        }
+    }
+    registry.set_defines(defines);
+
+    for id in args.rules.iter().chain(&args.disable) {
+        if registry.get(id).is_none() {
+            return Err(format!("Unknown rule `{id}`, see --list-rules"));
+        }
+    }
+
+    if !args.rules.is_empty() {
+        let ids: Vec<&str> = registry.rules().map(|rule| rule.id()).collect();
+        for id in ids {
+            registry.set_enabled(id, args.rules.iter().any(|r| r == id));
+        }
+    }
+    for id in &args.disable {
+        registry.set_enabled(id, false);
+    }
+
+    Ok(registry)
+}
+
+/// The code page of the command line or else of the config
+fn code_page(args: &Args, config: &Config) -> Result<u16, String> {
+    let code_page = args
+        .code_page
+        .or(config.code_page)
+        .unwrap_or(DEFAULT_CODE_PAGE);
+    code_page_encoding(code_page)?;
+    Ok(code_page)
+}
+
+/// Units, programs, libraries and packages are parsed, include files are only lexed.
+/// Diagnostics found in every configuration are not tagged with them
+fn check(registry: &Registry, path: &Path, content: &str) -> Vec<Diagnostic> {
+    let is_parsed = path.extension().is_some_and(|ext| {
+        ["pas", "dpr", "dpk"]
+            .iter()
+            .any(|parsed| ext.eq_ignore_ascii_case(parsed))
+    });
+
+    let mut diagnostics = if is_parsed {
+        registry.check(content)
+    } else {
+        registry.check_tokens(content)
+    };
+    for d in &mut diagnostics {
+        if d.configurations.len() == registry.configurations().len() {
+            d.configurations.clear();
+        }
+    }
+    diagnostics
+}
+
+/// The lexer counts characters, the columns are reported in the unit of the settings
+fn convert_columns(diagnostics: &mut [Diagnostic], content: &str, settings: &Settings) {
+    if settings.columns == ColumnUnit::Char {
+        return;
+    }
+    let index = LineIndex::new(content, settings.columns, settings.tab_width);
+    for d in diagnostics {
+        d.col = index.column(d.row, d.col);
+        d.end_col = index.column(d.end_row, d.end_col);
+    }
+}
+
+/// Prints the tokens or the syntax tree of a file, errors of the lexer and parser go to stderr
+fn dump(
+    args: &Args,
+    command: &Command,
+    explicit_config: Option<&Config>,
+) -> Result<ExitCode, String> {
+    let (Command::Tokens { file: path } | Command::Ast { file: path }) = command;
+    let dir = config_dir(path);
+    let settings = match explicit_config {
+        Some(config) => create_settings(args, config)?,
+        None => Config::find(&dir)
+            .and_then(|config| create_settings(args, &config))
+            .map_err(|err| format!("Config of {}: {err}", dir.display()))?,
+    };
+    let file = SourceFile::load(path, settings.code_page)?;
+
+    let (output, diagnostics) = match command {
+        Command::Tokens { .. } => dump_tokens(&file.text, args.format),
+        Command::Ast { .. } => {
+            dump_ast(&file.text, settings.registry.defines().clone(), args.format)
+        }
+    };
+    println!("{output}");
+    for d in &diagnostics {
+        eprintln!("{}", format_text(path, d));
+    }
+
+    Ok(if diagnostics.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_LINT_ERRORS)
+    })
+}
+
+/// One `row:col-row:col typ content` line per token, or a JSON array
+fn dump_tokens(content: &str, format: Format) -> (String, Vec<Diagnostic>) {
+    let mut lex = Lexer::new(content);
+    let tokens: Vec<Token> = lex.by_ref().collect();
+
+    let output = match format {
+        Format::Text => tokens
+            .iter()
+            .map(|tok| {
+                let Span { start, end } = tok.span;
+                format!(
+                    "{}:{}-{}:{} {:?} {:?}",
+                    start.row, start.col, end.row, end.col, tok.typ, tok.content
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Format::Json => serde_json::to_string_pretty(&tokens).unwrap(),
+    };
+    (output, lex.diagnostics().to_vec())
+}
+
+/// The parsed `File`, pretty-printed with `Debug` or as JSON
+fn dump_ast(content: &str, defines: Defines, format: Format) -> (String, Vec<Diagnostic>) {
+    let mut parser = DelphiParser::with_defines(content, defines);
+    let file = parser.parse_file();
+
+    let output = match format {
+        Format::Text => format!("{file:#?}"),
+        Format::Json => serde_json::to_string_pretty(&file).unwrap(),
+    };
+    (output, parser.diagnostics())
+}
+
+fn print_results(args: &Args, results: &[(&PathBuf, Vec<Diagnostic>)]) {
+    match args.format {
+        Format::Text => {
+            for (path, diagnostics) in results {
+                for d in diagnostics {
+                    println!("{}", format_text(path, d));
+                }
+            }
+
+            if !args.quiet {
+                let count = |severity| {
+                    results
+                        .iter()
+                        .flat_map(|(_, diagnostics)| diagnostics)
+                        .filter(|d| d.severity == severity)
+                        .count()
+                };
+                println!(
+                    "{} files checked: {} errors, {} warnings, {} infos",
+                    results.len(),
+                    count(Severity::Error),
+                    count(Severity::Warning),
+                    count(Severity::Info)
+                );
+            }
+        }
+        Format::Json => {
+            let all: Vec<FileDiagnostic> = results
+                .iter()
+                .flat_map(|(path, diagnostics)| {
+                    diagnostics.iter().map(|diagnostic| FileDiagnostic {
+                        file: path.display().to_string(),
+                        diagnostic,
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&all).unwrap());
+        }
+    }
+}
+
+fn format_text(path: &Path, d: &Diagnostic) -> String {
+    let mut res = format!(
+        "{}:{}:{}: {} [{}] {}",
+        path.display(),
+        d.row,
+        d.col,
+        d.severity,
+        d.code,
+        d.message
+    );
+    if !d.configurations.is_empty() {
+        res += &format!(" ({})", d.configurations.join(", "));
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn arguments() {
+        Args::command().debug_assert();
+
+        let args = Args::try_parse_from([
+            "delphi_linter",
+            "src",
+            "--rules",
+            "todo-comment,empty-method",
+            "--severity",
+            "warning",
+        ])
+        .unwrap();
+        assert_eq!(args.rules, vec!["todo-comment", "empty-method"]);
+        assert_eq!(args.severity, Severity::Warning);
+
+        assert!(Args::try_parse_from(["delphi_linter"]).is_err());
+        assert!(Args::try_parse_from(["delphi_linter", "--list-rules"]).is_ok());
+        assert!(Args::try_parse_from(["delphi_linter", "a.pas", "-q", "-v"]).is_err());
+    }
+
+    #[test]
+    fn rule_selection() {
+        let args =
+            Args::try_parse_from(["delphi_linter", "a.pas", "--rules", "todo-comment"]).unwrap();
+        let registry = create_registry(&args, &Config::default()).unwrap();
+        assert!(registry.is_enabled("todo-comment"));
+        assert!(!registry.is_enabled("empty-method"));
+
+        let args =
+            Args::try_parse_from(["delphi_linter", "a.pas", "--disable", "todo-comment"]).unwrap();
+        let registry = create_registry(&args, &Config::default()).unwrap();
+        assert!(!registry.is_enabled("todo-comment"));
+        assert!(registry.is_enabled("empty-method"));
+
+        let args = Args::try_parse_from(["delphi_linter", "a.pas", "--rules", "nope"]).unwrap();
+        assert!(create_registry(&args, &Config::default()).is_err());
+    }
+
+    #[test]
+    fn command_line_overrides_config() {
+        let config = Config::parse(
+            "[rules.todo-comment]\nenabled = false\n[rules.empty-method]\nenabled = true",
+        )
+        .unwrap();
+        let args =
+            Args::try_parse_from(["delphi_linter", "a.pas", "--rules", "todo-comment"]).unwrap();
+
+        let registry = create_registry(&args, &config).unwrap();
+
+        assert!(registry.is_enabled("todo-comment"));
+        assert!(registry.defines().symbols().is_empty());
+        assert!(!registry.is_enabled("empty-method"));
+    }
+
+    #[test]
+    fn defines() {
+        let config = Config::parse("defines = [\"MSWINDOWS\"]").unwrap();
+        let args = Args::try_parse_from([
+            "delphi_linter",
+            "a.pas",
+            "-D",
+            "DEBUG",
+            "--define",
+            "A,B,CompilerVersion=36.0",
+        ])
+        .unwrap();
+
+        let registry = create_registry(&args, &config).unwrap();
+
+        assert_eq!(
+            registry.defines().symbols(),
+            vec!["A", "B", "DEBUG", "MSWINDOWS"]
+        );
+        assert_eq!(registry.defines().value("CompilerVersion"), Some(36.0));
+
+        let args = Args::try_parse_from(["delphi_linter", "a.pas", "-D", "X=new"]).unwrap();
+        assert_eq!(
+            create_registry(&args, &config).err(),
+            Some(String::from("Invalid value of `X`: `new`"))
+        );
+    }
+
+    #[test]
+    fn code_pages() {
+        let config = Config::parse("code_page = 1251").unwrap();
+
+        let args = Args::try_parse_from(["delphi_linter", "a.pas"]).unwrap();
+        assert_eq!(code_page(&args, &Config::default()), Ok(1252));
+        assert_eq!(code_page(&args, &config), Ok(1251));
+
+        let args = Args::try_parse_from(["delphi_linter", "a.pas", "--code-page", "932"]).unwrap();
+        assert_eq!(code_page(&args, &config), Ok(932));
+
+        let args = Args::try_parse_from(["delphi_linter", "a.pas", "--code-page", "1"]).unwrap();
+        assert!(code_page(&args, &config).is_err());
+    }
+
+    #[test]
+    fn encodings() {
+        let args = Args::try_parse_from(["delphi_linter", "a.pas"]).unwrap();
+        let registry = create_registry(&args, &Config::default()).unwrap();
+        let source =
+            "unit Foo;\r\ninterface\r\ntype\r\n  Größe = Integer;\r\nimplementation\r\nend.";
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(source.encode_utf16().flat_map(u16::to_le_bytes));
+        let mut utf8_bom = vec![0xEF, 0xBB, 0xBF];
+        utf8_bom.extend(source.bytes());
+        let (ansi, _, _) = encoding_rs::WINDOWS_1252.encode(source);
+
+        for bytes in [utf16, utf8_bom, ansi.into_owned()] {
+            let file = SourceFile::decode(&bytes, DEFAULT_CODE_PAGE).unwrap();
+            let diagnostics = check(&registry, Path::new("a.pas"), &file.text);
+            assert_eq!(
+                diagnostics
+                    .iter()
+                    .map(|d| format_text(Path::new("a.pas"), d))
+                    .collect::<Vec<_>>(),
+                vec!["a.pas:4:3: warning [type-prefix] Type `Größe` should start with `T`"],
+                "{}",
+                file.encoding
+            );
+        }
+    }
+
+    #[test]
+    fn columns() {
+        let config = Config::parse("columns = \"utf16\"\ntab_width = 4").unwrap();
+        let source = "unit Foo;\rinterface\rtype\r{😀}\tGröße = Integer;\rimplementation\rend.";
+        let lines = |args: &[&str]| {
+            let args = Args::try_parse_from([&["delphi_linter", "a.pas"], args].concat()).unwrap();
+            let settings = create_settings(&args, &config).unwrap();
+            let mut diagnostics = check(&settings.registry, Path::new("a.pas"), source);
+            convert_columns(&mut diagnostics, source, &settings);
+            diagnostics
+                .iter()
+                .map(|d| format!("{}:{}", d.row, d.col))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines(&["--columns", "char"]), vec!["4:5"]);
+        assert_eq!(lines(&[]), vec!["4:6"]);
+        assert_eq!(lines(&["--columns", "utf8"]), vec!["4:8"]);
+        assert_eq!(lines(&["--columns", "display"]), vec!["4:5"]);
+        assert_eq!(
+            lines(&["--columns", "display", "--tab-width", "8"]),
+            vec!["4:9"]
+        );
+        assert!(Args::try_parse_from(["delphi_linter", "a.pas", "--columns", "bytes"]).is_err());
+    }
+
+    #[test]
+    fn subcommands() {
+        let args =
+            Args::try_parse_from(["delphi_linter", "tokens", "a.pas", "--format", "json"]).unwrap();
+        assert!(
+            matches!(&args.command, Some(Command::Tokens { file }) if file == Path::new("a.pas"))
+        );
+        assert!(args.format == Format::Json);
+
+        let args = Args::try_parse_from(["delphi_linter", "-D", "DEBUG", "ast", "a.pas"]).unwrap();
+        assert!(matches!(args.command, Some(Command::Ast { .. })));
+        assert_eq!(args.defines, vec!["DEBUG"]);
+
+        assert!(Args::try_parse_from(["delphi_linter", "tokens"]).is_err());
+    }
+
+    #[test]
+    fn token_dump() {
+        let (text, diagnostics) = dump_tokens("x := 'a';\r\n$", Format::Text);
+        assert_eq!(
+            text,
+            "1:1-1:2 Identifier \"x\"
+1:2-1:3 Whitespace \" \"
+1:3-1:5 Symbol(Assign) \":=\"
+1:5-1:6 Whitespace \" \"
+1:6-1:9 String(\"a\") \"'a'\"
+1:9-1:10 Symbol(Semicolon) \";\"
+1:10-2:1 Whitespace \"\\r\\n\"
+2:1-2:2 Unknown \"$\""
+        );
+        assert_eq!(diagnostics.len(), 1);
+
+        let (json, _) = dump_tokens("x := 'a';", Format::Json);
+        let tokens: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(tokens[0]["typ"], "Identifier");
+        assert_eq!(tokens[0]["span"]["end"]["col"], 2);
+        assert_eq!(tokens[2]["typ"]["Symbol"], "Assign");
+        assert_eq!(tokens[4]["typ"]["String"], "a");
+        assert_eq!(tokens[4]["content"], "'a'");
+    }
+
+    #[test]
+    fn ast_dump() {
+        let source =
+            "unit Foo;\ninterface\n{$IFDEF A}type TA = Integer;{$ENDIF}\nimplementation\nend.";
+
+        let (json, diagnostics) = dump_ast(source, Defines::new(&["A"]), Format::Json);
+        assert_eq!(diagnostics, vec![]);
+        let file: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(file["Unit"]["name"], "Foo");
+        assert_eq!(file["Unit"]["interface"][0]["Definition"][0]["name"], "TA");
+
+        let (text, _) = dump_ast(source, Defines::default(), Format::Text);
+        assert!(
+            text.starts_with("Unit(\n    Unit {\n        name: \"Foo\",\n        interface: [],")
+        );
+
+        let (_, diagnostics) = dump_ast("unit Foo; begin", Defines::default(), Format::Text);
+        assert!(!diagnostics.is_empty());
+    }
+
+    #[test]
+    fn project_files() {
+        let args = Args::try_parse_from(["delphi_linter", "a.dpr"]).unwrap();
+        let registry = create_registry(&args, &Config::default()).unwrap();
+        let lines = |path: &str, content: &str| {
+            check(&registry, Path::new(path), content)
+                .iter()
+                .map(|d| format_text(Path::new(path), d))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lines(
+                "a.dpr",
+                "program A;\nuses B in 'B.pas';\ntype\n  Foo = Integer;\nbegin\nend."
+            ),
+            vec!["a.dpr:4:3: warning [type-prefix] Type `Foo` should start with `T`"]
+        );
+        assert_eq!(
+            lines(
+                "a.dpk",
+                "package A;\nrequires rtl;\ncontains B in 'B.pas'\nend."
+            ),
+            vec!["a.dpk:4:1: error [syntax-error] Expected `;`, found `end`"]
+        );
+        // Include files are not parsed
+        assert_eq!(lines("a.inc", "Foo = Integer;"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn text_format() {
+        let d = Diagnostic::new(
+            Severity::Warning,
+            "empty-method",
+            String::from("Method `Foo` is empty"),
+            3,
+            11,
+        );
+
+        assert_eq!(
+            format_text(Path::new("a.pas"), &d),
+            "a.pas:3:11: warning [empty-method] Method `Foo` is empty"
+        );
+    }
+
+    #[test]
+    fn configurations() {
+        let config =
+            Config::parse("[[configurations]]\nWin32 = [\"MSWINDOWS\"]\nLinux64 = [\"LINUX\"]")
+                .unwrap();
+        let args = Args::try_parse_from(["delphi_linter", "a.pas"]).unwrap();
+        let registry = create_registry(&args, &config).unwrap();
+
+        let diagnostics = check(
+            &registry,
+            Path::new("a.pas"),
+            "unit Foo;
+interface
+type
+  Foo = Integer;
+  {$IFDEF LINUX}
+  Bar = Integer;
+  {$ENDIF}
+implementation
+end.",
+        );
+
+        let lines: Vec<String> = diagnostics
+            .iter()
+            .map(|d| format_text(Path::new("a.pas"), d))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "a.pas:4:3: warning [type-prefix] Type `Foo` should start with `T`",
+                "a.pas:6:3: warning [type-prefix] Type `Bar` should start with `T` (Linux64)",
+            ]
+        );
+    }
 }
@@ -1,26 +1,4095 @@
+use std::collections::VecDeque;
 
-use crate::lexer::{Lexer, Token};
+use crate::ast::*;
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Span, Symbol, Token, TokenTyp};
+use crate::preprocessor::{Defines, Preprocessor};
+use crate::reader::Position;
 
-pub struct Parser {
-    lex: Lexer,
+const METHOD_DIRECTIVES: [&str; 29] = [
+    "abstract",
+    "assembler",
+    "cdecl",
+    "deprecated",
+    "dispid",
+    "dynamic",
+    "experimental",
+    "export",
+    "external",
+    "far",
+    "final",
+    "forward",
+    "inline",
+    "library",
+    "message",
+    "near",
+    "overload",
+    "override",
+    "pascal",
+    "platform",
+    "register",
+    "reintroduce",
+    "safecall",
+    "static",
+    "stdcall",
+    "unsafe",
+    "varargs",
+    "virtual",
+    "winapi",
+];
+
+/// Hints after the declaration of a variable or constant, `deprecated` can have a message
+const HINT_DIRECTIVES: [&str; 4] = ["deprecated", "experimental", "library", "platform"];
+
+/// Keywords that open a block which is closed by `end`
+pub(crate) const BLOCK_KEYWORDS: [&str; 4] = ["begin", "case", "try", "asm"];
+
+/// Keywords that end a statement list
+const STATEMENT_END_KEYWORDS: [&str; 7] = [
+    "end",
+    "else",
+    "until",
+    "except",
+    "finally",
+    "initialization",
+    "finalization",
+];
+
+/// Tokens that can start a new part of a unit, used to resynchronize after a syntax error
+const SECTION_KEYWORDS: [&str; 18] = [
+    "uses",
+    "exports",
+    "const",
+    "resourcestring",
+    "type",
+    "var",
+    "threadvar",
+    "label",
+    "procedure",
+    "function",
+    "constructor",
+    "destructor",
+    "interface",
+    "implementation",
+    "initialization",
+    "finalization",
+    "class",
+    "end",
+];
+
+/// Deeper nested expressions and statements are reported instead of overflowing the stack
+const MAX_NESTING: usize = 100;
+
+type ParseResult<T> = Result<T, Diagnostic>;
+
+pub struct Parser<'a> {
+    lex: Preprocessor<'a>,
+    buffer: VecDeque<Token<'a>>,
+    diagnostics: Vec<Diagnostic>,
+    /// Number of tokens consumed so far, used to make sure error recovery makes progress
+    consumed: usize,
+    nesting: usize,
+    /// End of the last consumed token
+    last_end: Position,
 }
 
-impl Parser {
-    pub fn new(content: String) -> Self {
-        Parser { lex: Lexer::new(content) }
+impl<'a> Parser<'a> {
+    pub fn new(content: &'a str) -> Self {
+        Parser::with_defines(content, Defines::default())
+    }
+
+    /// A parser that only sees the conditional branches that are active for `defines`
+    pub fn with_defines(content: &'a str, defines: Defines) -> Self {
+        Parser {
+            lex: Preprocessor::new(content, defines),
+            buffer: VecDeque::new(),
+            diagnostics: Vec::new(),
+            consumed: 0,
+            nesting: 0,
+            last_end: Position::default(),
+        }
+    }
+
+    /// Syntax errors of the lexer and the parser sorted by position
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut res: Vec<Diagnostic> = self.lex.diagnostics();
+        res.extend(self.diagnostics.iter().cloned());
+        res.sort_by_key(|d| (d.row, d.col));
+        res
+    }
+
+    /// Parses a `unit`, `program`, `library` or `package` depending on its first keyword,
+    /// anything else is parsed as a unit. Syntax errors are collected in `diagnostics` and the
+    /// parser continues after them, so the returned `File` contains everything it could read
+    pub fn parse_file(&mut self) -> File {
+        if self.is_keyword("program") {
+            File::Program(self.parse_program("program"))
+        } else if self.is_keyword("library") {
+            File::Library(self.parse_program("library"))
+        } else if self.is_keyword("package") {
+            File::Package(self.parse_package())
+        } else {
+            File::Unit(self.parse_unit())
+        }
+    }
+
+    /// Parses a complete `unit` file, see `parse_file`
+    pub fn parse_unit(&mut self) -> Unit {
+        let start = self.consumed;
+        let res = self.parse_header("unit");
+        let name = self.recover_section(res, start).unwrap_or_default();
+
+        let res = self.expect_keyword("interface");
+        self.report(res);
+        let interface = self.parse_interface_section();
+
+        let res = self.expect_keyword("implementation");
+        self.report(res);
+        let implementation = self.parse_implementation_section();
+
+        let mut initialization = Vec::new();
+        let mut finalization = Vec::new();
+
+        if self.is_keyword("initialization") || self.is_keyword("begin") {
+            self.next();
+            initialization = self.parse_statement_list();
+        }
+        if self.is_keyword("finalization") {
+            self.next();
+            finalization = self.parse_statement_list();
+        }
+
+        let res = self.parse_file_end();
+        self.report(res);
+
+        Unit {
+            name,
+            interface,
+            implementation,
+            initialization,
+            finalization,
+        }
+    }
+
+    /// A `program` or `library` file with the declarations and the main block
+    fn parse_program(&mut self, kind: &str) -> Program {
+        let start = self.consumed;
+        let res = self.parse_header(kind);
+        let name = self.recover_section(res, start).unwrap_or_default();
+
+        let declarations = self.parse_implementation_section();
+
+        // Libraries without initialization code can end with just `end.`
+        let mut body = Vec::new();
+        if self.is_keyword("begin") || kind == "program" {
+            let res = self.expect_keyword("begin");
+            self.report(res);
+            body = self.parse_statement_list();
+        }
+
+        let res = self.parse_file_end();
+        self.report(res);
+
+        Program {
+            name,
+            declarations,
+            body,
+        }
+    }
+
+    /// A `package` file, the `requires` and `contains` clauses are both optional
+    fn parse_package(&mut self) -> Package {
+        let start = self.consumed;
+        let res = self.parse_header("package");
+        let name = self.recover_section(res, start).unwrap_or_default();
+
+        let mut requires = Vec::new();
+        if self.is_keyword("requires") {
+            let res = self.parse_requires();
+            requires = self.recover(res, &["contains", "end"]).unwrap_or_default();
+        }
+        let mut contains = Vec::new();
+        if self.is_keyword("contains") {
+            self.next();
+            let res = self.parse_imports();
+            contains = self.recover(res, &["end"]).unwrap_or_default();
+        }
+
+        let res = self.parse_file_end();
+        self.report(res);
+
+        Package {
+            name,
+            requires,
+            contains,
+        }
+    }
+
+    /// `unit Foo;`, `program Foo(Input, Output);` or the header of a library or package
+    fn parse_header(&mut self, kind: &str) -> ParseResult<String> {
+        self.expect_keyword(kind)?;
+        let name = self.parse_qualified_name()?;
+        self.skip_until_symbol(Symbol::Semicolon)?;
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        Ok(name)
+    }
+
+    /// `end.` and nothing but whitespace and comments after it
+    /// `end.`, the compiler ignores everything after it, so the tokens after it are not read
+    fn parse_file_end(&mut self) -> ParseResult<()> {
+        self.expect_keyword("end")?;
+        self.expect_symbol(Symbol::Dot)?;
+        Ok(())
+    }
+
+    fn parse_interface_section(&mut self) -> Vec<Interface> {
+        let mut res = Vec::new();
+
+        while !self.is_keyword("implementation") && self.peek().typ != TokenTyp::EOF {
+            let start = self.consumed;
+
+            let part = if self.is_keyword("uses") {
+                self.parse_uses().map(Interface::Imports)
+            } else if self.is_keyword("const") || self.is_keyword("resourcestring") {
+                self.parse_const_section().map(Interface::Constant)
+            } else if self.is_keyword("type") {
+                self.parse_type_section().map(Interface::Definition)
+            } else if self.is_keyword("var") || self.is_keyword("threadvar") {
+                self.parse_var_section().map(Interface::Variable)
+            } else if self.is_method_start() {
+                self.parse_method_head()
+                    .map(|head| Interface::Method(Box::new(head)))
+            } else {
+                Err(self.error("Expected a declaration"))
+            };
+
+            if let Some(part) = self.recover_section(part, start) {
+                res.push(part);
+            }
+        }
+
+        res
+    }
+
+    fn parse_implementation_section(&mut self) -> Vec<Implementation> {
+        let mut res = Vec::new();
+
+        loop {
+            let start = self.consumed;
+
+            let part = if self.is_keyword("uses") {
+                self.parse_uses().map(Implementation::Imports)
+            } else if self.is_keyword("exports") {
+                self.parse_exports().map(Implementation::Exports)
+            } else if let Some(part) = self.parse_declaration() {
+                part
+            } else if self.is_unit_end() {
+                break;
+            } else {
+                Err(self.error("Expected a declaration"))
+            };
+
+            if let Some(part) = self.recover_section(part, start) {
+                res.push(part);
+            }
+        }
+
+        res
+    }
+
+    /// `initialization`, `finalization`, `begin` or `end.` after the implementation section
+    fn is_unit_end(&mut self) -> bool {
+        let tok = self.peek();
+        if tok.typ == TokenTyp::EOF {
+            return true;
+        }
+        if tok.typ != TokenTyp::Keyword {
+            return false;
+        }
+
+        match tok.content.to_lowercase().as_str() {
+            "initialization" | "finalization" | "begin" => true,
+            "end" => self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Dot),
+            _ => false,
+        }
+    }
+
+    /// Parses const, type, var and method declarations until something else is found
+    fn parse_declarations(&mut self) -> ParseResult<Vec<Implementation>> {
+        let mut res = Vec::new();
+
+        while let Some(part) = self.parse_declaration() {
+            res.push(part?);
+        }
+
+        Ok(res)
+    }
+
+    /// `None` if the next token does not start a declaration
+    fn parse_declaration(&mut self) -> Option<ParseResult<Implementation>> {
+        let part = if self.is_keyword("const") || self.is_keyword("resourcestring") {
+            self.parse_const_section().map(Implementation::Constant)
+        } else if self.is_keyword("type") {
+            self.parse_type_section().map(Implementation::Definition)
+        } else if self.is_keyword("var") || self.is_keyword("threadvar") {
+            self.parse_var_section().map(Implementation::Variable)
+        } else if self.is_keyword("label") {
+            self.parse_label_section().map(Implementation::Label)
+        } else if self.is_method_start() {
+            self.parse_method()
+                .map(|method| Implementation::Method(Box::new(method)))
+        } else {
+            return None;
+        };
+
+        Some(part)
+    }
+
+    fn parse_uses(&mut self) -> ParseResult<Vec<Import>> {
+        self.expect_keyword("uses")?;
+        self.parse_imports()
+    }
+
+    /// The units of a `uses` or `contains` clause up to the `;`
+    fn parse_imports(&mut self) -> ParseResult<Vec<Import>> {
+        let mut res = Vec::new();
+
+        loop {
+            let name = self.parse_qualified_name()?;
+            let mut path = None;
+            if self.is_keyword("in") {
+                self.next();
+                path = Some(self.expect_string()?);
+            }
+            res.push(Import { name, path });
+
+            if self.is_symbol(Symbol::Comma) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        Ok(res)
+    }
+
+    /// `requires rtl, vcl;` of a package
+    fn parse_requires(&mut self) -> ParseResult<Vec<String>> {
+        self.expect_keyword("requires")?;
+        let mut res = vec![self.parse_qualified_name()?];
+        while self.is_symbol(Symbol::Comma) {
+            self.next();
+            res.push(self.parse_qualified_name()?);
+        }
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        Ok(res)
+    }
+
+    /// `exports Foo, Bar(A: Integer) name 'Bar1' index 2;` of a library or unit
+    fn parse_exports(&mut self) -> ParseResult<Vec<Export>> {
+        self.expect_keyword("exports")?;
+        let mut res = Vec::new();
+
+        loop {
+            let name = self.parse_qualified_name()?;
+            let mut params = None;
+            if self.is_symbol(Symbol::LeftParen) {
+                params = Some(self.parse_parameters()?);
+            }
+            let mut export = Export {
+                name,
+                params,
+                export_name: None,
+                index: None,
+            };
+            loop {
+                if self.is_keyword("name") {
+                    self.next();
+                    export.export_name = Some(self.parse_expression()?);
+                } else if self.is_keyword("index") {
+                    self.next();
+                    export.index = Some(self.parse_expression()?);
+                } else if self.is_keyword("resident") {
+                    // Ignored by the compiler since Delphi 2
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+            res.push(export);
+
+            if self.is_symbol(Symbol::Comma) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        Ok(res)
+    }
+
+    fn parse_const_section(&mut self) -> ParseResult<Vec<Constant>> {
+        self.next();
+        let mut res = Vec::new();
+
+        while self.is_declaration_start(&[Symbol::Equal, Symbol::Colon]) {
+            let constant = self.parse_constant();
+            if let Some(constant) = self.recover(constant, &[";", "implementation"]) {
+                res.push(constant);
+            }
+            self.expect_symbol(Symbol::Semicolon)?;
+        }
+
+        Ok(res)
+    }
+
+    fn parse_constant(&mut self) -> ParseResult<Constant> {
+        let name = self.expect_identifier()?;
+        let mut typ = None;
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            typ = Some(self.parse_type()?);
+        }
+        self.expect_symbol(Symbol::Equal)?;
+        let value = self.parse_expression()?;
+        self.skip_hints();
+
+        Ok(Constant { name, typ, value })
+    }
+
+    fn parse_var_section(&mut self) -> ParseResult<Vec<Variable>> {
+        self.next();
+        let mut res = Vec::new();
+
+        while self.is_declaration_start(&[Symbol::Colon, Symbol::Comma]) {
+            let variable = self.parse_variable();
+            if let Some(variable) = self.recover(variable, &[";", "implementation"]) {
+                res.push(variable);
+            }
+            self.expect_symbol(Symbol::Semicolon)?;
+        }
+
+        Ok(res)
+    }
+
+    /// `A, B: Type [absolute X | = Value]` without the trailing `;`
+    fn parse_variable(&mut self) -> ParseResult<Variable> {
+        let names = self.parse_identifier_list()?;
+        self.expect_symbol(Symbol::Colon)?;
+        let typ = self.parse_type()?;
+
+        let mut absolute = None;
+        let mut value = None;
+        if self.is_keyword("absolute") {
+            self.next();
+            absolute = Some(self.parse_qualified_name()?);
+        } else if self.is_symbol(Symbol::Equal) {
+            self.next();
+            value = Some(self.parse_expression()?);
+        }
+        self.skip_hints();
+
+        Ok(Variable {
+            names,
+            typ,
+            absolute,
+            value,
+        })
+    }
+
+    /// `platform`, `deprecated 'Use Bar'` and the other hints, they don't change the meaning
+    fn skip_hints(&mut self) {
+        while HINT_DIRECTIVES.iter().any(|hint| self.is_keyword(hint)) {
+            let hint = self.next();
+            if hint.content.eq_ignore_ascii_case("deprecated")
+                && matches!(self.peek().typ, TokenTyp::String(_))
+            {
+                self.next();
+            }
+        }
+    }
+
+    fn parse_label_section(&mut self) -> ParseResult<Vec<String>> {
+        self.expect_keyword("label")?;
+        let mut res = vec![self.next().content.into_owned()];
+
+        while self.is_symbol(Symbol::Comma) {
+            self.next();
+            res.push(self.next().content.into_owned());
+        }
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        Ok(res)
+    }
+
+    fn parse_type_section(&mut self) -> ParseResult<Vec<TypeDeclaration>> {
+        self.expect_keyword("type")?;
+        let mut res = Vec::new();
+
+        loop {
+            self.skip_attributes()?;
+            if !self.is_declaration_start(&[Symbol::Equal, Symbol::Less]) {
+                break;
+            }
+
+            let declaration = self.parse_type_declaration();
+            if let Some(declaration) = self.recover(declaration, &[";", "implementation"]) {
+                res.push(declaration);
+            } else if self.is_symbol(Symbol::Semicolon) {
+                self.next();
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn parse_type_declaration(&mut self) -> ParseResult<TypeDeclaration> {
+        let name_tok = self.expect_identifier_token()?;
+        let mut generic_params = Vec::new();
+        if self.is_symbol(Symbol::Less) {
+            generic_params = self.parse_generic_params()?;
+        }
+        self.expect_symbol(Symbol::Equal)?;
+        // `TMyInt = type Integer` declares a distinct type
+        if self.is_keyword("type") {
+            self.next();
+        }
+
+        let definition = self.parse_type_definition()?;
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        // Calling conventions of procedural types like `TProc = procedure; stdcall;`
+        if let TypeDefinition::Alias(Type::Procedure(_)) = definition {
+            self.parse_method_directives()?;
+        }
+
+        Ok(TypeDeclaration {
+            name: name_tok.content.into_owned(),
+            generic_params,
+            definition,
+            row: name_tok.span.start.row,
+            col: name_tok.span.start.col,
+        })
+    }
+
+    /// `<T, U: class, constructor; V>`
+    fn parse_generic_params(&mut self) -> ParseResult<Vec<GenericParam>> {
+        self.expect_symbol(Symbol::Less)?;
+        let mut res = Vec::new();
+
+        loop {
+            let names = self.parse_identifier_list()?;
+
+            let mut constraints = Vec::new();
+            if self.is_symbol(Symbol::Colon) {
+                self.next();
+                loop {
+                    constraints.push(self.expect_name()?);
+                    if self.is_symbol(Symbol::Comma) {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+            }
+
+            for name in names {
+                res.push(GenericParam {
+                    name,
+                    constraints: constraints.clone(),
+                });
+            }
+
+            if self.is_symbol(Symbol::Semicolon) || self.is_symbol(Symbol::Comma) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_symbol(Symbol::Greater)?;
+
+        Ok(res)
+    }
+
+    /// The right side of a type declaration
+    fn parse_type_definition(&mut self) -> ParseResult<TypeDefinition> {
+        let packed = self.is_keyword("packed");
+        if packed {
+            self.next();
+        }
+
+        let next_is_end = self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Semicolon);
+        let kind = match self.peek().content.to_lowercase().as_str() {
+            "class" if self.peek_nth(1).content.eq_ignore_ascii_case("of") => None,
+            "class" => Some(ClassKind::Class),
+            "interface" => Some(ClassKind::Interface),
+            "dispinterface" => Some(ClassKind::DispInterface),
+            "record" => Some(ClassKind::Record),
+            "object" => Some(ClassKind::Object),
+            _ => None,
+        };
+
+        match kind {
+            Some(kind) if next_is_end => {
+                self.next();
+                Ok(TypeDefinition::Forward(kind))
+            }
+            Some(kind) => Ok(TypeDefinition::Class(self.parse_class(kind, packed)?)),
+            None => Ok(TypeDefinition::Alias(self.parse_type()?)),
+        }
+    }
+
+    fn parse_class(&mut self, mut kind: ClassKind, packed: bool) -> ParseResult<Class> {
+        self.next();
+
+        let mut is_abstract = false;
+        let mut is_sealed = false;
+        if self.is_keyword("abstract") {
+            self.next();
+            is_abstract = true;
+        } else if self.peek().content.eq_ignore_ascii_case("sealed") {
+            self.next();
+            is_sealed = true;
+        }
+
+        if self.peek().content.eq_ignore_ascii_case("helper") {
+            self.next();
+            kind = match kind {
+                ClassKind::Record => ClassKind::RecordHelper,
+                _ => ClassKind::ClassHelper,
+            };
+        }
+
+        let mut ancestors = Vec::new();
+        if self.is_symbol(Symbol::LeftParen) {
+            self.next();
+            loop {
+                ancestors.push(self.parse_type_name()?);
+                if self.is_symbol(Symbol::Comma) {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+            self.expect_symbol(Symbol::RightParen)?;
+        }
+
+        let mut helper_for = None;
+        if matches!(kind, ClassKind::ClassHelper | ClassKind::RecordHelper) {
+            self.expect_keyword("for")?;
+            helper_for = Some(self.parse_type_name()?);
+        }
+
+        // Only interfaces have a GUID, in other kinds `[...]` are attributes of the first member
+        let is_interface = matches!(kind, ClassKind::Interface | ClassKind::DispInterface);
+        let mut class = Class {
+            kind,
+            packed,
+            is_abstract,
+            is_sealed,
+            ancestors,
+            helper_for,
+            guid: None,
+            sections: Vec::new(),
+            variant_part: None,
+        };
+
+        // `TFoo = class(TBar);` has no body
+        if self.is_symbol(Symbol::Semicolon) {
+            return Ok(class);
+        }
+
+        if is_interface && self.is_symbol(Symbol::LeftBracket) {
+            self.next();
+            // A string or the name of a GUID constant
+            let tok = self.next();
+            class.guid = Some(match tok.typ {
+                TokenTyp::String(value) => value,
+                _ => tok.content.into_owned(),
+            });
+            self.expect_symbol(Symbol::RightBracket)?;
+        }
+
+        let mut section = ClassSection {
+            visibility: None,
+            members: Vec::new(),
+        };
+
+        loop {
+            self.skip_attributes()?;
+
+            if self.is_keyword("end") || self.peek().typ == TokenTyp::EOF {
+                break;
+            }
+            if self.is_keyword("case") {
+                class.variant_part = Some(self.parse_variant_part()?);
+                break;
+            }
+
+            if let Some(visibility) = self.parse_visibility() {
+                if section.visibility.is_some() || !section.members.is_empty() {
+                    class.sections.push(section);
+                }
+                section = ClassSection {
+                    visibility: Some(visibility),
+                    members: Vec::new(),
+                };
+                continue;
+            }
+
+            let start = self.consumed;
+            let members = self.parse_class_members();
+            let sync = [
+                ";",
+                "end",
+                "private",
+                "protected",
+                "public",
+                "published",
+                "strict",
+            ];
+            if let Some(mut members) = self.recover(members, &sync) {
+                section.members.append(&mut members);
+            } else if self.is_symbol(Symbol::Semicolon) || self.consumed == start {
+                self.next();
+            }
+        }
+        self.expect_keyword("end")?;
+
+        if section.visibility.is_some() || !section.members.is_empty() {
+            class.sections.push(section);
+        }
+
+        Ok(class)
+    }
+
+    fn parse_visibility(&mut self) -> Option<Visibility> {
+        let is_strict = self.peek().content.eq_ignore_ascii_case("strict");
+        let tok = if is_strict {
+            self.peek_nth(1)
+        } else {
+            self.peek()
+        };
+
+        let visibility = match (is_strict, tok.content.to_lowercase().as_str()) {
+            (true, "private") => Visibility::StrictPrivate,
+            (true, "protected") => Visibility::StrictProtected,
+            (false, "private") => Visibility::Private,
+            (false, "protected") => Visibility::Protected,
+            (false, "public") => Visibility::Public,
+            (false, "published") => Visibility::Published,
+            (false, "automated") => Visibility::Automated,
+            _ => return None,
+        };
+
+        self.next();
+        if is_strict {
+            self.next();
+        }
+        Some(visibility)
+    }
+
+    fn parse_class_members(&mut self) -> ParseResult<Vec<ClassMember>> {
+        if self.is_keyword("const") {
+            return Ok(vec![ClassMember::Constant(self.parse_const_section()?)]);
+        }
+        if self.is_keyword("type") {
+            return Ok(vec![ClassMember::Definition(self.parse_type_section()?)]);
+        }
+        if self.is_keyword("property")
+            || (self.is_keyword("class")
+                && self.peek_nth(1).content.eq_ignore_ascii_case("property"))
+        {
+            return Ok(vec![ClassMember::Property(Box::new(
+                self.parse_property()?,
+            ))]);
+        }
+        if self.is_method_start() {
+            return Ok(vec![ClassMember::Method(Box::new(
+                self.parse_method_head()?,
+            ))]);
+        }
+
+        let is_class_var =
+            self.is_keyword("class") && self.peek_nth(1).content.eq_ignore_ascii_case("var");
+        if is_class_var {
+            self.next();
+        }
+        let is_var_section = self.is_keyword("var");
+        if is_var_section {
+            self.next();
+        }
+
+        if !self.is_identifier(0) {
+            return Err(self.error("Expected a class member"));
+        }
+
+        let mut res = Vec::new();
+        loop {
+            let field = self.parse_variable()?;
+            self.expect_symbol(Symbol::Semicolon)?;
+            res.push(if is_class_var {
+                ClassMember::ClassVar(field)
+            } else {
+                ClassMember::Field(field)
+            });
+
+            // A `var` section continues until the next member that is not a field
+            self.skip_attributes()?;
+            let next_is_field = self.is_declaration_start(&[Symbol::Colon, Symbol::Comma]);
+            if !(is_class_var || is_var_section) || !next_is_field {
+                break;
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn parse_property(&mut self) -> ParseResult<Property> {
+        let is_class = self.is_keyword("class");
+        if is_class {
+            self.next();
+        }
+        self.expect_keyword("property")?;
+
+        let mut property = Property {
+            name: self.expect_name()?,
+            is_class,
+            params: Vec::new(),
+            typ: None,
+            index: None,
+            read: None,
+            write: None,
+            stored: None,
+            default: None,
+            nodefault: false,
+            implements: Vec::new(),
+            is_default: false,
+        };
+
+        if self.is_symbol(Symbol::LeftBracket) {
+            property.params =
+                self.parse_parameter_list(Symbol::LeftBracket, Symbol::RightBracket)?;
+        }
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            property.typ = Some(self.parse_type()?);
+        }
+
+        while !self.is_symbol(Symbol::Semicolon) {
+            let specifier = self.next();
+            match specifier.content.to_lowercase().as_str() {
+                "index" => property.index = Some(self.parse_expression()?),
+                "read" => property.read = Some(self.parse_qualified_name()?),
+                "write" => property.write = Some(self.parse_qualified_name()?),
+                "stored" => property.stored = Some(self.parse_expression()?),
+                "default" => property.default = Some(self.parse_expression()?),
+                "nodefault" => property.nodefault = true,
+                "implements" => loop {
+                    property.implements.push(self.parse_qualified_name()?);
+                    if self.is_symbol(Symbol::Comma) {
+                        self.next();
+                    } else {
+                        break;
+                    }
+                },
+                "dispid" => {
+                    self.parse_expression()?;
+                }
+                "readonly" | "writeonly" => {}
+                _ => return Err(self.error_at(&specifier, "Expected a property specifier")),
+            }
+        }
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        let is_default = self.peek().content.eq_ignore_ascii_case("default")
+            && self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Semicolon);
+        if is_default {
+            self.next();
+            self.next();
+            property.is_default = true;
+        }
+
+        Ok(property)
+    }
+
+    /// `case Tag: Integer of 0: (A: Integer); 1: (B: Byte);`
+    fn parse_variant_part(&mut self) -> ParseResult<VariantPart> {
+        self.expect_keyword("case")?;
+
+        let mut tag = None;
+        if self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Colon) {
+            tag = Some(self.expect_identifier()?);
+            self.next();
+        }
+        let tag_type = self.parse_type()?;
+        self.expect_keyword("of")?;
+
+        let mut branches = Vec::new();
+        while !self.is_keyword("end") && !self.is_symbol(Symbol::RightParen) {
+            let labels = self.parse_expression_list()?;
+            self.expect_symbol(Symbol::Colon)?;
+            self.expect_symbol(Symbol::LeftParen)?;
+
+            let mut fields = Vec::new();
+            let mut variant_part = None;
+            while !self.is_symbol(Symbol::RightParen) {
+                if self.is_keyword("case") {
+                    variant_part = Some(Box::new(self.parse_variant_part()?));
+                    break;
+                }
+                fields.push(self.parse_variable()?);
+                if self.is_symbol(Symbol::Semicolon) {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+            self.expect_symbol(Symbol::RightParen)?;
+
+            branches.push(VariantBranch {
+                labels,
+                fields,
+                variant_part,
+            });
+
+            if self.is_symbol(Symbol::Semicolon) {
+                self.next();
+            }
+        }
+
+        Ok(VariantPart {
+            tag,
+            tag_type,
+            branches,
+        })
+    }
+
+    /// Skips attributes like `[Weak]` in front of declarations
+    fn skip_attributes(&mut self) -> ParseResult<()> {
+        while self.is_symbol(Symbol::LeftBracket) {
+            self.next();
+            self.skip_until_symbol(Symbol::RightBracket)?;
+            self.expect_symbol(Symbol::RightBracket)?;
+        }
+        Ok(())
+    }
+
+    fn is_method_start(&mut self) -> bool {
+        if self.is_keyword("class") {
+            let tok = self.peek_nth(1);
+            return [
+                "procedure",
+                "function",
+                "constructor",
+                "destructor",
+                "operator",
+            ]
+            .contains(&tok.content.to_lowercase().as_str());
+        }
+
+        let tok = self.peek();
+        tok.typ == TokenTyp::Keyword
+            && ["procedure", "function", "constructor", "destructor"]
+                .contains(&tok.content.to_lowercase().as_str())
+    }
+
+    fn parse_method_head(&mut self) -> ParseResult<MethodHead> {
+        let is_class = self.is_keyword("class");
+        if is_class {
+            self.next();
+        }
+
+        let kind_tok = self.next();
+        let kind = match kind_tok.content.to_lowercase().as_str() {
+            "procedure" => MethodKind::Procedure,
+            "function" => MethodKind::Function,
+            "constructor" => MethodKind::Constructor,
+            "destructor" => MethodKind::Destructor,
+            "operator" => MethodKind::Operator,
+            _ => return Err(self.error_at(&kind_tok, "Expected a method")),
+        };
+
+        // Names like `TFoo<T>.Bar<U>`, the generic parameters of the method itself are kept
+        let start = self.peek().span.start;
+        let (row, col) = (start.row, start.col);
+        let mut name = self.expect_name()?;
+        let mut generic_params = Vec::new();
+        loop {
+            if self.is_symbol(Symbol::Less) {
+                generic_params = self.parse_generic_params()?;
+            }
+            if self.is_symbol(Symbol::Dot) && self.is_name(1) {
+                self.next();
+                name.push('.');
+                name += &self.expect_name()?;
+            } else {
+                break;
+            }
+        }
+
+        let mut params = Vec::new();
+        if self.is_symbol(Symbol::LeftParen) {
+            params = self.parse_parameters()?;
+        }
+
+        let mut return_type = None;
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            return_type = Some(self.parse_type()?);
+        }
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        let directives = self.parse_method_directives()?;
+
+        Ok(MethodHead {
+            kind,
+            is_class,
+            name,
+            generic_params,
+            params,
+            return_type,
+            directives,
+            row,
+            col,
+        })
+    }
+
+    fn parse_parameters(&mut self) -> ParseResult<Vec<Parameter>> {
+        self.parse_parameter_list(Symbol::LeftParen, Symbol::RightParen)
+    }
+
+    fn parse_parameter_list(&mut self, open: Symbol, close: Symbol) -> ParseResult<Vec<Parameter>> {
+        self.expect_symbol(open)?;
+        let mut res = Vec::new();
+
+        while !self.is_symbol(close) {
+            self.skip_attributes()?;
+            let modifier = match self.peek().content.to_lowercase().as_str() {
+                "var" => Some(ParameterModifier::Var),
+                "const" => Some(ParameterModifier::Const),
+                // `Out: Integer` is a parameter named `Out`
+                "out" if self.is_identifier(1) => Some(ParameterModifier::Out),
+                _ => None,
+            };
+            if modifier.is_some() {
+                self.next();
+            }
+
+            let names = self.parse_identifier_list()?;
+
+            let mut typ = None;
+            if self.is_symbol(Symbol::Colon) {
+                self.next();
+                typ = Some(self.parse_type()?);
+            }
+
+            let mut default = None;
+            if self.is_symbol(Symbol::Equal) {
+                self.next();
+                default = Some(self.parse_expression()?);
+            }
+
+            res.push(Parameter {
+                modifier,
+                names,
+                typ,
+                default,
+            });
+
+            if self.is_symbol(Symbol::Semicolon) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_symbol(close)?;
+
+        Ok(res)
+    }
+
+    /// An identifier followed by one of `symbols`, e.g. `Name =` in a const section
+    fn is_declaration_start(&mut self, symbols: &[Symbol]) -> bool {
+        self.is_identifier(0)
+            && matches!(self.peek_nth(1).typ, TokenTyp::Symbol(symbol) if symbols.contains(&symbol))
+    }
+
+    /// Parses `override; overload;` and similar after a method head
+    fn parse_method_directives(&mut self) -> ParseResult<Vec<MethodDirective>> {
+        let mut res = Vec::new();
+
+        loop {
+            let directive = self.peek().content.to_lowercase();
+            let is_field = matches!(
+                self.peek_nth(1).typ,
+                TokenTyp::Symbol(Symbol::Colon | Symbol::Comma)
+            );
+            if !METHOD_DIRECTIVES.contains(&directive.as_str()) || is_field {
+                break;
+            }
+
+            self.next();
+            // Arguments like `message WM_PAINT` or `external 'user32.dll' name 'Foo'`
+            self.skip_until_symbol(Symbol::Semicolon)?;
+            self.expect_symbol(Symbol::Semicolon)?;
+
+            res.push(match directive.as_str() {
+                "abstract" => MethodDirective::Abstract,
+                "virtual" => MethodDirective::Virtual,
+                "dynamic" => MethodDirective::Dynamic,
+                "override" => MethodDirective::Override,
+                "overload" => MethodDirective::Overload,
+                "reintroduce" => MethodDirective::Reintroduce,
+                "static" => MethodDirective::Static,
+                "inline" => MethodDirective::Inline,
+                "final" => MethodDirective::Final,
+                "forward" => MethodDirective::Forward,
+                "external" => MethodDirective::External,
+                "message" => MethodDirective::Message,
+                _ => MethodDirective::Other(directive),
+            });
+        }
+
+        Ok(res)
+    }
+
+    fn parse_method(&mut self) -> ParseResult<Method> {
+        let head = self.parse_method_head()?;
+
+        if head
+            .directives
+            .iter()
+            .any(|d| *d == MethodDirective::Forward || *d == MethodDirective::External)
+        {
+            return Ok(Method {
+                head,
+                declarations: Vec::new(),
+                body: None,
+            });
+        }
+
+        let declarations = self.parse_declarations()?;
+        let body = self.parse_method_body()?;
+        self.expect_symbol(Symbol::Semicolon)?;
+
+        Ok(Method {
+            head,
+            declarations,
+            body: Some(body),
+        })
+    }
+
+    /// `begin ... end` or `asm ... end` of a method
+    fn parse_method_body(&mut self) -> ParseResult<Vec<Statement>> {
+        if self.is_keyword("asm") {
+            let start = self.peek().span.start;
+            let asm = self.parse_asm()?;
+            return Ok(vec![Statement::new(asm, self.span_from(start))]);
+        }
+
+        self.expect_keyword("begin")?;
+        let body = self.parse_statement_list();
+        self.expect_end()?;
+
+        Ok(body)
+    }
+
+    /// Parses statements separated by `;` until a keyword that ends the list,
+    /// broken statements are reported and skipped
+    fn parse_statement_list(&mut self) -> Vec<Statement> {
+        let mut res = Vec::new();
+        let mut sync = vec![";"];
+        sync.extend(STATEMENT_END_KEYWORDS);
+
+        loop {
+            let start = self.consumed;
+            let statement = self.parse_statement();
+            if let Some(Some(statement)) = self.recover(statement, &sync) {
+                res.push(statement);
+            }
+
+            if self.is_symbol(Symbol::Semicolon) {
+                self.next();
+            } else if self.is_statement_list_end() {
+                break;
+            } else {
+                let err = self.error("Expected `;`");
+                self.report::<()>(Err(err));
+                if self.consumed == start {
+                    self.next();
+                }
+            }
+        }
+
+        res
+    }
+
+    fn is_statement_list_end(&mut self) -> bool {
+        let tok = self.peek();
+        match tok.typ {
+            TokenTyp::EOF => true,
+            TokenTyp::Keyword => {
+                STATEMENT_END_KEYWORDS.contains(&tok.content.to_lowercase().as_str())
+            }
+            _ => false,
+        }
+    }
+
+    /// Parses a single statement, `None` for an empty statement
+    fn parse_statement(&mut self) -> ParseResult<Option<Statement>> {
+        self.nested(Self::parse_statement_inner)
+    }
+
+    /// Picks the parse function first, so the recursion only goes through one call site and
+    /// keeps the stack frame small
+    fn parse_statement_inner(&mut self) -> ParseResult<Option<Statement>> {
+        let tok = self.peek();
+        let start = tok.span.start;
+
+        let parse: fn(&mut Self) -> ParseResult<StatementKind> = if tok.typ == TokenTyp::Keyword {
+            match tok.content.to_lowercase().as_str() {
+                "begin" => Self::parse_compound,
+                "if" => Self::parse_if,
+                "case" => Self::parse_case,
+                "for" => Self::parse_for,
+                "while" => Self::parse_while,
+                "repeat" => Self::parse_repeat,
+                "try" => Self::parse_try,
+                "with" => Self::parse_with,
+                "raise" => Self::parse_raise,
+                "goto" => Self::parse_goto,
+                "asm" => Self::parse_asm,
+                "var" => Self::parse_inline_variable,
+                "const" => Self::parse_inline_constant,
+                // Empty statements
+                "end" | "else" | "until" | "except" | "finally" | "finalization" => {
+                    return Ok(None);
+                }
+                _ => Self::parse_simple_statement,
+            }
+        } else if matches!(tok.typ, TokenTyp::EOF | TokenTyp::Symbol(Symbol::Semicolon)) {
+            return Ok(None);
+        } else if matches!(
+            tok.typ,
+            TokenTyp::Identifier | TokenTyp::Directive | TokenTyp::Number(_)
+        ) && self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Colon)
+        {
+            Self::parse_labeled
+        } else {
+            match self.peek().content.to_lowercase().as_str() {
+                "exit" => Self::parse_exit,
+                "break" => |parser| {
+                    parser.next();
+                    Ok(StatementKind::Break)
+                },
+                "continue" => |parser| {
+                    parser.next();
+                    Ok(StatementKind::Continue)
+                },
+                _ => Self::parse_simple_statement,
+            }
+        };
+
+        let kind = parse(self)?;
+        Ok(Some(Statement::new(kind, self.span_from(start))))
+    }
+
+    /// `begin ... end`
+    fn parse_compound(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        let body = self.parse_statement_list();
+        self.expect_end()?;
+        Ok(StatementKind::Compound(body))
+    }
+
+    fn parse_while(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        let condition = self.parse_expression()?;
+        self.expect_keyword("do")?;
+        let body = self.parse_statement()?.map(Box::new);
+        Ok(StatementKind::While { condition, body })
+    }
+
+    fn parse_repeat(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        let body = self.parse_statement_list();
+        self.expect_keyword("until")?;
+        let condition = self.parse_expression()?;
+        Ok(StatementKind::Repeat { body, condition })
+    }
+
+    fn parse_with(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        let objects = self.parse_expression_list()?;
+        self.expect_keyword("do")?;
+        let body = self.parse_statement()?.map(Box::new);
+        Ok(StatementKind::With { objects, body })
+    }
+
+    fn parse_goto(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+        Ok(StatementKind::Goto(self.next().content.into_owned()))
+    }
+
+    /// `Label: Statement`
+    fn parse_labeled(&mut self) -> ParseResult<StatementKind> {
+        let label = self.next().content.into_owned();
+        self.next();
+        let statement = self.parse_statement()?.map(Box::new);
+        Ok(StatementKind::Labeled { label, statement })
+    }
+
+    /// `Exit` or `Exit(Result)`
+    fn parse_exit(&mut self) -> ParseResult<StatementKind> {
+        self.next();
+
+        let mut value = None;
+        if self.is_symbol(Symbol::LeftParen) {
+            self.next();
+            if !self.is_symbol(Symbol::RightParen) {
+                value = Some(self.parse_expression()?);
+            }
+            self.expect_symbol(Symbol::RightParen)?;
+        }
+
+        Ok(StatementKind::Exit(value))
+    }
+
+    /// Assignments and calls
+    fn parse_simple_statement(&mut self) -> ParseResult<StatementKind> {
+        let target = self.parse_expression()?;
+
+        if self.is_symbol(Symbol::Assign) {
+            self.next();
+            let value = self.parse_expression()?;
+            return Ok(StatementKind::Assignment { target, value });
+        }
+
+        Ok(StatementKind::Call(target))
+    }
+
+    fn parse_if(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("if")?;
+        let condition = self.parse_expression()?;
+        self.expect_keyword("then")?;
+        let then_branch = self.parse_statement()?.map(Box::new);
+
+        let mut else_branch = None;
+        if self.is_keyword("else") {
+            self.next();
+            else_branch = self.parse_statement()?.map(Box::new);
+        }
+
+        Ok(StatementKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    fn parse_case(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("case")?;
+        let selector = self.parse_expression()?;
+        self.expect_keyword("of")?;
+
+        let mut branches = Vec::new();
+        while !self.is_keyword("else") && !self.is_keyword("end") {
+            let labels = self.parse_expression_list()?;
+            self.expect_symbol(Symbol::Colon)?;
+            let body = self.parse_statement()?;
+            branches.push(CaseBranch { labels, body });
+
+            if self.is_symbol(Symbol::Semicolon) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+
+        let mut else_branch = None;
+        if self.is_keyword("else") {
+            self.next();
+            else_branch = Some(self.parse_statement_list());
+        }
+        self.expect_end()?;
+
+        Ok(StatementKind::Case {
+            selector,
+            branches,
+            else_branch,
+        })
+    }
+
+    fn parse_for(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("for")?;
+        let inline_var = self.is_keyword("var");
+        if inline_var {
+            self.next();
+        }
+        let variable = self.expect_identifier()?;
+
+        let mut typ = None;
+        if inline_var && self.is_symbol(Symbol::Colon) {
+            self.next();
+            typ = Some(Box::new(self.parse_type()?));
+        }
+
+        if self.is_keyword("in") {
+            self.next();
+            let collection = self.parse_expression()?;
+            self.expect_keyword("do")?;
+            let body = self.parse_statement()?.map(Box::new);
+
+            return Ok(StatementKind::ForIn {
+                inline_var,
+                variable,
+                typ,
+                collection,
+                body,
+            });
+        }
+
+        self.expect_symbol(Symbol::Assign)?;
+        let start = self.parse_expression()?;
+        let downto = self.is_keyword("downto");
+        if downto {
+            self.next();
+        } else {
+            self.expect_keyword("to")?;
+        }
+        let end = self.parse_expression()?;
+        self.expect_keyword("do")?;
+        let body = self.parse_statement()?.map(Box::new);
+
+        Ok(StatementKind::For {
+            inline_var,
+            variable,
+            typ,
+            start,
+            end,
+            downto,
+            body,
+        })
+    }
+
+    fn parse_try(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("try")?;
+        let body = self.parse_statement_list();
+
+        let handler = if self.is_keyword("finally") {
+            self.next();
+            TryHandler::Finally(self.parse_statement_list())
+        } else {
+            self.expect_keyword("except")?;
+            let mut handlers = Vec::new();
+            let mut statements = Vec::new();
+
+            if self.is_keyword("on") {
+                while self.is_keyword("on") {
+                    handlers.push(self.parse_exception_handler()?);
+                    if self.is_symbol(Symbol::Semicolon) {
+                        self.next();
+                    }
+                }
+                if self.is_keyword("else") {
+                    self.next();
+                    statements = self.parse_statement_list();
+                }
+            } else {
+                statements = self.parse_statement_list();
+            }
+
+            TryHandler::Except(ExceptBlock {
+                handlers,
+                statements,
+            })
+        };
+        self.expect_end()?;
+
+        Ok(StatementKind::Try { body, handler })
+    }
+
+    /// `on E: Exception do Statement`
+    fn parse_exception_handler(&mut self) -> ParseResult<ExceptionHandler> {
+        self.expect_keyword("on")?;
+
+        let mut variable = None;
+        if self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Colon) {
+            variable = Some(self.expect_identifier()?);
+            self.next();
+        }
+        let typ = self.parse_qualified_name()?;
+        self.expect_keyword("do")?;
+        let body = self.parse_statement()?;
+
+        Ok(ExceptionHandler {
+            variable,
+            typ,
+            body,
+        })
+    }
+
+    fn parse_raise(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("raise")?;
+
+        let mut exception = None;
+        let mut at = None;
+        if !self.is_statement_end() {
+            exception = Some(self.parse_expression()?);
+            if self.peek().content.eq_ignore_ascii_case("at") {
+                self.next();
+                at = Some(self.parse_expression()?);
+            }
+        }
+
+        Ok(StatementKind::Raise { exception, at })
+    }
+
+    /// The lexer turns everything up to `end` into `TokenTyp::Asm` tokens
+    fn parse_asm(&mut self) -> ParseResult<StatementKind> {
+        let start = self.peek().span.start;
+        self.expect_keyword("asm")?;
+        let mut tokens = Vec::new();
+        while self.peek().typ == TokenTyp::Asm {
+            tokens.push(self.next().into_owned());
+        }
+        self.expect_keyword("end")?;
+
+        Ok(StatementKind::Asm(AsmBlock {
+            tokens,
+            row: start.row,
+            col: start.col,
+        }))
+    }
+
+    /// `var X: Integer := 0` inside a method body
+    fn parse_inline_variable(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("var")?;
+        let names = self.parse_identifier_list()?;
+
+        let mut typ = None;
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            typ = Some(self.parse_type()?);
+        }
+
+        let mut value = None;
+        if self.is_symbol(Symbol::Assign) {
+            self.next();
+            value = Some(self.parse_expression()?);
+        }
+
+        Ok(StatementKind::Variable { names, typ, value })
+    }
+
+    /// `const X = 0` inside a method body
+    fn parse_inline_constant(&mut self) -> ParseResult<StatementKind> {
+        self.expect_keyword("const")?;
+        let name = self.expect_identifier()?;
+
+        let mut typ = None;
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            typ = Some(self.parse_type()?);
+        }
+        self.expect_symbol(Symbol::Equal)?;
+        let value = self.parse_expression()?;
+
+        Ok(StatementKind::Constant { name, typ, value })
+    }
+
+    fn is_statement_end(&mut self) -> bool {
+        let tok = self.peek();
+        match tok.typ {
+            TokenTyp::EOF => true,
+            TokenTyp::Symbol(symbol) => symbol == Symbol::Semicolon,
+            TokenTyp::Keyword => ["end", "else", "until", "except", "finally"]
+                .contains(&tok.content.to_lowercase().as_str()),
+            _ => false,
+        }
+    }
+
+    fn parse_expression_list(&mut self) -> ParseResult<Vec<Expression>> {
+        let mut res = vec![self.parse_range()?];
+
+        while self.is_symbol(Symbol::Comma) {
+            self.next();
+            res.push(self.parse_range()?);
+        }
+
+        Ok(res)
+    }
+
+    /// An expression optionally followed by `..` and the end of a range
+    fn parse_range(&mut self) -> ParseResult<Expression> {
+        let start = self.parse_expression()?;
+
+        if self.is_symbol(Symbol::DotDot) {
+            self.next();
+            let end = self.parse_expression()?;
+            let span = self.span_from(start.span.start);
+            let kind = ExpressionKind::Range(Box::new(start), Box::new(end));
+            return Ok(Expression::new(kind, span));
+        }
+
+        Ok(start)
+    }
+
+    /// Relational operators have the lowest precedence
+    pub fn parse_expression(&mut self) -> ParseResult<Expression> {
+        self.nested(Self::parse_expression_inner)
+    }
+
+    fn parse_expression_inner(&mut self) -> ParseResult<Expression> {
+        let mut left = self.parse_simple_expression()?;
+
+        while let Some(op) = self.peek_binary_op(
+            &[
+                (Symbol::Equal, BinaryOp::Equal),
+                (Symbol::NotEqual, BinaryOp::NotEqual),
+                (Symbol::Less, BinaryOp::Less),
+                (Symbol::LessEqual, BinaryOp::LessEqual),
+                (Symbol::Greater, BinaryOp::Greater),
+                (Symbol::GreaterEqual, BinaryOp::GreaterEqual),
+            ],
+            &[("in", BinaryOp::In), ("is", BinaryOp::Is)],
+        ) {
+            self.next();
+            let right = self.parse_simple_expression()?;
+            let span = self.span_from(left.span.start);
+            let kind = ExpressionKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+            left = Expression::new(kind, span);
+        }
+
+        Ok(left)
+    }
+
+    /// Additive operators
+    fn parse_simple_expression(&mut self) -> ParseResult<Expression> {
+        let left = self.parse_term()?;
+        self.parse_simple_expression_rest(left)
+    }
+
+    /// The additive operators after the first term `left`
+    fn parse_simple_expression_rest(&mut self, mut left: Expression) -> ParseResult<Expression> {
+        while let Some(op) = self.peek_binary_op(
+            &[
+                (Symbol::Plus, BinaryOp::Add),
+                (Symbol::Minus, BinaryOp::Subtract),
+            ],
+            &[("or", BinaryOp::Or), ("xor", BinaryOp::Xor)],
+        ) {
+            self.next();
+            let right = self.parse_term()?;
+            let span = self.span_from(left.span.start);
+            let kind = ExpressionKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+            left = Expression::new(kind, span);
+        }
+
+        Ok(left)
+    }
+
+    /// Multiplicative operators
+    fn parse_term(&mut self) -> ParseResult<Expression> {
+        let left = self.parse_factor()?;
+        self.parse_term_rest(left)
+    }
+
+    /// The multiplicative operators after the first factor `left`
+    fn parse_term_rest(&mut self, mut left: Expression) -> ParseResult<Expression> {
+        while let Some(op) = self.peek_binary_op(
+            &[
+                (Symbol::Star, BinaryOp::Multiply),
+                (Symbol::Slash, BinaryOp::Divide),
+            ],
+            &[
+                ("div", BinaryOp::Div),
+                ("mod", BinaryOp::Mod),
+                ("and", BinaryOp::And),
+                ("shl", BinaryOp::Shl),
+                ("shr", BinaryOp::Shr),
+                ("as", BinaryOp::As),
+            ],
+        ) {
+            self.next();
+            let right = self.parse_factor()?;
+            let span = self.span_from(left.span.start);
+            let kind = ExpressionKind::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(right),
+            };
+            left = Expression::new(kind, span);
+        }
+
+        Ok(left)
+    }
+
+    fn peek_binary_op(
+        &mut self,
+        symbols: &[(Symbol, BinaryOp)],
+        keywords: &[(&str, BinaryOp)],
+    ) -> Option<BinaryOp> {
+        let tok = self.peek();
+        match tok.typ {
+            TokenTyp::Symbol(symbol) => symbols
+                .iter()
+                .find(|(s, _)| *s == symbol)
+                .map(|(_, op)| op.clone()),
+            TokenTyp::Keyword => keywords
+                .iter()
+                .find(|(content, _)| tok.content.eq_ignore_ascii_case(content))
+                .map(|(_, op)| op.clone()),
+            _ => None,
+        }
+    }
+
+    /// Unary operators bind the strongest
+    fn parse_factor(&mut self) -> ParseResult<Expression> {
+        let tok = self.peek();
+        let start = tok.span.start;
+        let op = match tok.typ {
+            TokenTyp::Keyword if tok.content.eq_ignore_ascii_case("not") => Some(UnaryOp::Not),
+            TokenTyp::Symbol(Symbol::Minus) => Some(UnaryOp::Negate),
+            TokenTyp::Symbol(Symbol::Plus) => Some(UnaryOp::Plus),
+            TokenTyp::Symbol(Symbol::At | Symbol::DoubleAt) => Some(UnaryOp::AddressOf),
+            _ => None,
+        };
+
+        if let Some(op) = op {
+            self.next();
+            let operand = self.parse_factor()?;
+            let kind = ExpressionKind::Unary {
+                op,
+                operand: Box::new(operand),
+            };
+            return Ok(Expression::new(kind, self.span_from(start)));
+        }
+
+        let primary = self.parse_primary()?;
+        self.parse_postfix(primary)
+    }
+
+    /// Member access, calls, indexing and dereferencing
+    fn parse_postfix(&mut self, mut expr: Expression) -> ParseResult<Expression> {
+        let start = expr.span.start;
+        loop {
+            if self.is_symbol(Symbol::Dot) {
+                self.next();
+                let name = self.expect_name()?;
+                let kind = ExpressionKind::Member {
+                    object: Box::new(expr),
+                    name,
+                };
+                expr = Expression::new(kind, self.span_from(start));
+            } else if self.is_symbol(Symbol::LeftParen) {
+                self.next();
+                let mut args = Vec::new();
+                if !self.is_symbol(Symbol::RightParen) {
+                    args = self.parse_expression_list()?;
+                }
+                self.expect_symbol(Symbol::RightParen)?;
+                let kind = ExpressionKind::Call {
+                    callee: Box::new(expr),
+                    args,
+                };
+                expr = Expression::new(kind, self.span_from(start));
+            } else if self.is_symbol(Symbol::Less)
+                && matches!(
+                    expr.kind,
+                    ExpressionKind::Identifier(_) | ExpressionKind::Member { .. }
+                )
+                && self.is_generic_args()
+            {
+                let args = self.parse_generic_args()?;
+                let kind = ExpressionKind::Generic {
+                    object: Box::new(expr),
+                    args,
+                };
+                expr = Expression::new(kind, self.span_from(start));
+            } else if self.is_symbol(Symbol::LeftBracket) {
+                self.next();
+                let indices = self.parse_expression_list()?;
+                self.expect_symbol(Symbol::RightBracket)?;
+                let kind = ExpressionKind::Index {
+                    object: Box::new(expr),
+                    indices,
+                };
+                expr = Expression::new(kind, self.span_from(start));
+            } else if self.is_symbol(Symbol::Caret) {
+                self.next();
+                let kind = ExpressionKind::Dereference(Box::new(expr));
+                expr = Expression::new(kind, self.span_from(start));
+            } else {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    /// Whether the `<` ahead starts type arguments instead of a comparison: only type names
+    /// up to the matching `>`, followed by `.` or `(` like in `TList<Integer>.Create`
+    fn is_generic_args(&mut self) -> bool {
+        let mut depth = 0;
+        let mut offset = 0;
+        loop {
+            let tok = self.peek_nth(offset);
+            match &tok.typ {
+                TokenTyp::Symbol(Symbol::Less) => depth += 1,
+                TokenTyp::Symbol(Symbol::Greater) => depth -= 1,
+                TokenTyp::Symbol(Symbol::Dot | Symbol::Comma)
+                | TokenTyp::Identifier
+                | TokenTyp::Directive => {}
+                TokenTyp::Keyword if tok.content.eq_ignore_ascii_case("string") => {}
+                _ => return false,
+            }
+            offset += 1;
+            if depth == 0 {
+                break;
+            }
+        }
+
+        matches!(
+            self.peek_nth(offset).typ,
+            TokenTyp::Symbol(Symbol::Dot | Symbol::LeftParen)
+        )
+    }
+
+    fn parse_primary(&mut self) -> ParseResult<Expression> {
+        let tok = self.peek();
+        let start = tok.span.start;
+
+        let kind = match tok.typ {
+            TokenTyp::Identifier | TokenTyp::Directive => {
+                ExpressionKind::Identifier(self.next().content.into_owned())
+            }
+            TokenTyp::Number(_) => ExpressionKind::Number(self.next().content.into_owned()),
+            TokenTyp::String(_) => ExpressionKind::String(self.expect_string()?),
+            TokenTyp::Symbol(Symbol::LeftParen) => self.parse_parenthesized()?,
+            TokenTyp::Symbol(Symbol::LeftBracket) => {
+                self.next();
+                let mut elements = Vec::new();
+                if !self.is_symbol(Symbol::RightBracket) {
+                    elements = self.parse_expression_list()?;
+                }
+                self.expect_symbol(Symbol::RightBracket)?;
+                ExpressionKind::Set(elements)
+            }
+            TokenTyp::Keyword => match tok.content.to_lowercase().as_str() {
+                "nil" => {
+                    self.next();
+                    ExpressionKind::Nil
+                }
+                // Typecasts like `string(P)`
+                "string" => ExpressionKind::Identifier(self.next().content.into_owned()),
+                "inherited" => {
+                    self.next();
+                    let mut call = None;
+                    if self.is_identifier(0) {
+                        let start = self.peek().span.start;
+                        let kind = ExpressionKind::Identifier(self.next().content.into_owned());
+                        let name = Expression::new(kind, self.span_from(start));
+                        call = Some(Box::new(self.parse_postfix(name)?));
+                    }
+                    ExpressionKind::Inherited(call)
+                }
+                "procedure" | "function" => self.parse_anonymous_method()?,
+                _ => return Err(self.error("Expected an expression")),
+            },
+            _ => return Err(self.error("Expected an expression")),
+        };
+
+        Ok(Expression::new(kind, self.span_from(start)))
+    }
+
+    /// `(A + B)`, array constants `(1, 2)` and record constants `(X: 1; Y: 2)`
+    fn parse_parenthesized(&mut self) -> ParseResult<ExpressionKind> {
+        self.expect_symbol(Symbol::LeftParen)?;
+
+        let is_record =
+            self.is_identifier(0) && self.peek_nth(1).typ == TokenTyp::Symbol(Symbol::Colon);
+        if is_record {
+            let mut fields = Vec::new();
+            while !self.is_symbol(Symbol::RightParen) {
+                let name = self.expect_identifier()?;
+                self.expect_symbol(Symbol::Colon)?;
+                fields.push((name, self.parse_expression()?));
+                if self.is_symbol(Symbol::Semicolon) {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+            self.expect_symbol(Symbol::RightParen)?;
+            return Ok(ExpressionKind::RecordConstant(fields));
+        }
+
+        let mut elements = vec![self.parse_expression()?];
+        while self.is_symbol(Symbol::Comma) {
+            self.next();
+            elements.push(self.parse_expression()?);
+        }
+        self.expect_symbol(Symbol::RightParen)?;
+
+        if elements.len() == 1 {
+            return Ok(elements.pop().unwrap().kind);
+        }
+        Ok(ExpressionKind::ArrayConstant(elements))
+    }
+
+    fn parse_anonymous_method(&mut self) -> ParseResult<ExpressionKind> {
+        let kind = if self.is_keyword("function") {
+            MethodKind::Function
+        } else {
+            MethodKind::Procedure
+        };
+        self.next();
+
+        let mut params = Vec::new();
+        if self.is_symbol(Symbol::LeftParen) {
+            params = self.parse_parameters()?;
+        }
+
+        let mut return_type = None;
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            return_type = Some(self.parse_type()?);
+        }
+
+        let declarations = self.parse_declarations()?;
+        let body = self.parse_method_body()?;
+
+        Ok(ExpressionKind::AnonymousMethod(Box::new(AnonymousMethod {
+            kind,
+            params,
+            return_type,
+            declarations,
+            body,
+        })))
+    }
+
+    fn parse_type(&mut self) -> ParseResult<Type> {
+        self.nested(Self::parse_type_inner)
+    }
+
+    fn parse_type_inner(&mut self) -> ParseResult<Type> {
+        let tok = self.peek().clone();
+
+        match (&tok.typ, tok.content.to_lowercase().as_str()) {
+            (TokenTyp::Keyword, "packed") => {
+                self.next();
+                if self.is_keyword("record") {
+                    return Ok(Type::Record(Box::new(
+                        self.parse_class(ClassKind::Record, true)?,
+                    )));
+                }
+                self.parse_type()
+            }
+            (TokenTyp::Keyword, "record") => Ok(Type::Record(Box::new(
+                self.parse_class(ClassKind::Record, false)?,
+            ))),
+            (TokenTyp::Keyword, "array") => self.parse_array_type(),
+            (TokenTyp::Keyword, "set") => {
+                self.next();
+                self.expect_keyword("of")?;
+                Ok(Type::Set(Box::new(self.parse_type()?)))
+            }
+            (TokenTyp::Keyword, "file") => {
+                self.next();
+                if self.is_keyword("of") {
+                    self.next();
+                    return Ok(Type::File(Some(Box::new(self.parse_type()?))));
+                }
+                Ok(Type::File(None))
+            }
+            (TokenTyp::Keyword, "class") => {
+                self.next();
+                self.expect_keyword("of")?;
+                Ok(Type::ClassOf(self.parse_type_name()?))
+            }
+            (TokenTyp::Keyword, "procedure" | "function") => self.parse_procedural_type(false),
+            (TokenTyp::Directive, "reference")
+                if self.peek_nth(1).content.eq_ignore_ascii_case("to") =>
+            {
+                self.next();
+                self.next();
+                self.parse_procedural_type(true)
+            }
+            (TokenTyp::Keyword, "string") => {
+                self.next();
+                if self.is_symbol(Symbol::LeftBracket) {
+                    self.next();
+                    let length = self.parse_expression()?;
+                    self.expect_symbol(Symbol::RightBracket)?;
+                    return Ok(Type::ShortString(length));
+                }
+                Ok(Type::Named(tok.content.into_owned()))
+            }
+            (TokenTyp::Symbol(Symbol::Caret), _) => {
+                self.next();
+                Ok(Type::Pointer(Box::new(self.parse_type()?)))
+            }
+            (TokenTyp::Symbol(Symbol::LeftParen), _) => self.parse_enum_type(),
+            (TokenTyp::Identifier | TokenTyp::Directive, _) => {
+                let start = tok.span.start;
+                let name = self.parse_qualified_name()?;
+
+                if self.is_subrange_bound() {
+                    // `Low(Integer)..High(Integer)`, the name starts the lower bound
+                    let span = self.span_from(start);
+                    let ident = Expression::new(ExpressionKind::Identifier(name), span);
+                    let left = self.parse_postfix(ident)?;
+                    let left = self.parse_term_rest(left)?;
+                    let start = self.parse_simple_expression_rest(left)?;
+                    self.expect_symbol(Symbol::DotDot)?;
+                    let end = self.parse_expression()?;
+                    return Ok(Type::Subrange(start, end));
+                }
+                if self.is_symbol(Symbol::Less) {
+                    let args = self.parse_generic_args()?;
+                    return Ok(Type::Generic { name, args });
+                }
+                Ok(Type::Named(name))
+            }
+            (TokenTyp::Number(_) | TokenTyp::String(_), _)
+            | (TokenTyp::Symbol(Symbol::Minus | Symbol::Plus), _) => {
+                let start = self.parse_expression()?;
+                self.expect_symbol(Symbol::DotDot)?;
+                let end = self.parse_expression()?;
+                Ok(Type::Subrange(start, end))
+            }
+            _ => Err(self.error("Expected a type")),
+        }
+    }
+
+    /// Whether the name before the current token is the start of a subrange bound instead of a
+    /// type, a type is never followed by `..`, a call or an arithmetic operator
+    fn is_subrange_bound(&mut self) -> bool {
+        let tok = self.peek();
+        match tok.typ {
+            TokenTyp::Symbol(symbol) => matches!(
+                symbol,
+                Symbol::DotDot
+                    | Symbol::LeftParen
+                    | Symbol::Plus
+                    | Symbol::Minus
+                    | Symbol::Star
+                    | Symbol::Slash
+            ),
+            TokenTyp::Keyword => ["div", "mod", "shl", "shr"]
+                .iter()
+                .any(|kw| tok.content.eq_ignore_ascii_case(kw)),
+            _ => false,
+        }
+    }
+
+    /// A type name like `TFoo` or `TList<Integer>` as written
+    fn parse_type_name(&mut self) -> ParseResult<String> {
+        let mut name = self.parse_qualified_name()?;
+
+        if self.is_symbol(Symbol::Less) {
+            let mut depth = 0;
+            loop {
+                if self.peek().typ == TokenTyp::EOF {
+                    return Err(self.error("Expected `>`"));
+                }
+                let tok = self.next();
+                match tok.typ {
+                    TokenTyp::Symbol(Symbol::Less) => depth += 1,
+                    TokenTyp::Symbol(Symbol::Greater) => depth -= 1,
+                    _ => {}
+                }
+                name += &tok.content;
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+
+        Ok(name)
+    }
+
+    /// `<Integer, TList<string>>`
+    fn parse_generic_args(&mut self) -> ParseResult<Vec<Type>> {
+        self.expect_symbol(Symbol::Less)?;
+        let mut res = vec![self.parse_type()?];
+
+        while self.is_symbol(Symbol::Comma) {
+            self.next();
+            res.push(self.parse_type()?);
+        }
+        self.expect_symbol(Symbol::Greater)?;
+
+        Ok(res)
+    }
+
+    fn parse_array_type(&mut self) -> ParseResult<Type> {
+        self.expect_keyword("array")?;
+
+        let mut dimensions = Vec::new();
+        if self.is_symbol(Symbol::LeftBracket) {
+            self.next();
+            loop {
+                dimensions.push(self.parse_type()?);
+                if self.is_symbol(Symbol::Comma) {
+                    self.next();
+                } else {
+                    break;
+                }
+            }
+            self.expect_symbol(Symbol::RightBracket)?;
+        }
+        self.expect_keyword("of")?;
+
+        if self.is_keyword("const") {
+            self.next();
+            return Ok(Type::ArrayOfConst);
+        }
+
+        Ok(Type::Array {
+            dimensions,
+            element: Box::new(self.parse_type()?),
+        })
+    }
+
+    fn parse_enum_type(&mut self) -> ParseResult<Type> {
+        self.expect_symbol(Symbol::LeftParen)?;
+        let mut values = Vec::new();
+
+        loop {
+            let name = self.expect_identifier()?;
+            let mut value = None;
+            if self.is_symbol(Symbol::Equal) {
+                self.next();
+                value = Some(self.parse_expression()?);
+            }
+            values.push(EnumValue { name, value });
+
+            if self.is_symbol(Symbol::Comma) {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        self.expect_symbol(Symbol::RightParen)?;
+
+        Ok(Type::Enum(values))
+    }
+
+    fn parse_procedural_type(&mut self, is_reference: bool) -> ParseResult<Type> {
+        let kind = if self.is_keyword("function") {
+            MethodKind::Function
+        } else {
+            MethodKind::Procedure
+        };
+        self.next();
+
+        let mut params = Vec::new();
+        if self.is_symbol(Symbol::LeftParen) {
+            params = self.parse_parameters()?;
+        }
+
+        let mut return_type = None;
+        if self.is_symbol(Symbol::Colon) {
+            self.next();
+            return_type = Some(self.parse_type()?);
+        }
+
+        let of_object = self.is_keyword("of");
+        if of_object {
+            self.next();
+            self.expect_keyword("object")?;
+        }
+
+        // Calling conventions without a `;` in front
+        while [
+            "cdecl", "pascal", "register", "safecall", "stdcall", "winapi",
+        ]
+        .contains(&self.peek().content.to_lowercase().as_str())
+        {
+            self.next();
+        }
+
+        Ok(Type::Procedure(Box::new(ProceduralType {
+            kind,
+            params,
+            return_type,
+            of_object,
+            is_reference,
+        })))
+    }
+
+    /// Parses dotted names like `System.SysUtils` or `TFoo.Bar`
+    fn parse_qualified_name(&mut self) -> ParseResult<String> {
+        let mut name = self.expect_name()?;
+
+        while self.is_symbol(Symbol::Dot) && self.is_name(1) {
+            self.next();
+            name.push('.');
+            name += &self.expect_name()?;
+        }
+
+        Ok(name)
+    }
+
+    fn parse_identifier_list(&mut self) -> ParseResult<Vec<String>> {
+        let mut res = vec![self.expect_identifier()?];
+
+        while self.is_symbol(Symbol::Comma) {
+            self.next();
+            res.push(self.expect_identifier()?);
+        }
+
+        Ok(res)
+    }
+
+    /// Collects the tokens of a value until one of `terminators` is found outside of brackets
+    fn parse_value_tokens(&mut self, terminators: &[Symbol]) -> ParseResult<Vec<Token<'a>>> {
+        let mut res = Vec::new();
+        let mut depth = 0;
+
+        loop {
+            let tok = self.peek();
+            if tok.typ == TokenTyp::EOF {
+                return Err(self.error("Unexpected end of file"));
+            }
+            if let TokenTyp::Symbol(symbol) = tok.typ {
+                match symbol {
+                    Symbol::LeftParen | Symbol::LeftBracket => depth += 1,
+                    Symbol::RightParen | Symbol::RightBracket if depth > 0 => depth -= 1,
+                    s if depth == 0 && terminators.contains(&s) => break,
+                    _ => {}
+                }
+            }
+            res.push(self.next());
+        }
+
+        Ok(res)
+    }
+    fn skip_until_symbol(&mut self, symbol: Symbol) -> ParseResult<()> {
+        self.parse_value_tokens(&[symbol])?;
+        Ok(())
+    }
+
+    /// Peeks at the next token that is not whitespace, a comment or a compiler directive
+    fn peek(&mut self) -> &Token<'a> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&mut self, offset: usize) -> &Token<'a> {
+        while self.buffer.len() <= offset {
+            let tok = self.lex.next();
+            // Unknown characters are already reported by the lexer
+            if !matches!(
+                tok.typ,
+                TokenTyp::Whitespace
+                    | TokenTyp::Comment
+                    | TokenTyp::DocComment
+                    | TokenTyp::ConditionalCompilation
+                    | TokenTyp::Unknown
+            ) {
+                self.buffer.push_back(tok);
+            }
+        }
+        &self.buffer[offset]
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        self.peek();
+        self.consumed += 1;
+        let tok = self.buffer.pop_front().unwrap();
+        self.last_end = tok.span.end;
+        tok
+    }
+
+    /// From `start` to the end of the last consumed token
+    fn span_from(&self, start: Position) -> Span {
+        Span {
+            start,
+            end: self.last_end,
+        }
+    }
+
+    /// Reserved words and directives
+    fn is_keyword(&mut self, kw: &str) -> bool {
+        let tok = self.peek();
+        matches!(tok.typ, TokenTyp::Keyword | TokenTyp::Directive)
+            && tok.content.eq_ignore_ascii_case(kw)
+    }
+
+    /// Directives are identifiers outside of the places where they have a meaning
+    fn is_identifier(&mut self, offset: usize) -> bool {
+        matches!(
+            self.peek_nth(offset).typ,
+            TokenTyp::Identifier | TokenTyp::Directive
+        )
+    }
+
+    fn is_symbol(&mut self, symbol: Symbol) -> bool {
+        self.peek().typ == TokenTyp::Symbol(symbol)
+    }
+
+    /// Identifiers and keywords are both valid after a `.`
+    fn is_name(&mut self, offset: usize) -> bool {
+        let tok = self.peek_nth(offset);
+        matches!(
+            tok.typ,
+            TokenTyp::Identifier | TokenTyp::Directive | TokenTyp::Keyword
+        )
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> ParseResult<Token<'a>> {
+        if self.is_keyword(kw) {
+            return Ok(self.next());
+        }
+        Err(self.error(&format!("Expected `{kw}`")))
+    }
+
+    /// The `end` of a block, tokens before it are reported and skipped
+    fn expect_end(&mut self) -> ParseResult<Token<'a>> {
+        if !self.is_keyword("end") && self.peek().typ != TokenTyp::EOF {
+            let err = self.error("Expected `end`");
+            self.report::<()>(Err(err));
+            self.synchronize(&["end"]);
+        }
+        self.expect_keyword("end")
+    }
+
+    fn expect_symbol(&mut self, symbol: Symbol) -> ParseResult<Token<'a>> {
+        if self.is_symbol(symbol) {
+            return Ok(self.next());
+        }
+        Err(self.error(&format!("Expected `{}`", symbol.as_str())))
+    }
+
+    /// The decoded value of a string literal
+    fn expect_string(&mut self) -> ParseResult<String> {
+        if let TokenTyp::String(value) = &self.peek().typ {
+            let value = value.clone();
+            self.next();
+            return Ok(value);
+        }
+        Err(self.error("Expected String"))
+    }
+
+    fn expect_identifier(&mut self) -> ParseResult<String> {
+        Ok(self.expect_identifier_token()?.content.into_owned())
+    }
+
+    fn expect_identifier_token(&mut self) -> ParseResult<Token<'a>> {
+        if self.is_identifier(0) {
+            return Ok(self.next());
+        }
+        Err(self.error("Expected Identifier"))
+    }
+
+    fn expect_name(&mut self) -> ParseResult<String> {
+        if self.is_name(0) {
+            return Ok(self.next().content.into_owned());
+        }
+        Err(self.error("Expected Identifier"))
+    }
+
+    fn error(&mut self, message: &str) -> Diagnostic {
+        let tok = self.peek().clone();
+        self.error_at(&tok, message)
+    }
+
+    fn error_at(&self, tok: &Token, message: &str) -> Diagnostic {
+        let found = if tok.typ == TokenTyp::EOF {
+            String::from("end of file")
+        } else {
+            format!("`{}`", tok.content)
+        };
+        Diagnostic::syntax_error(tok, format!("{message}, found {found}"))
+    }
+
+    /// Runs `f` one nesting level deeper, so deeply nested input is an error instead of a stack overflow
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> ParseResult<T>) -> ParseResult<T> {
+        if self.nesting >= MAX_NESTING {
+            return Err(self.error("Nesting too deep"));
+        }
+        self.nesting += 1;
+        let res = f(self);
+        self.nesting -= 1;
+        res
+    }
+
+    /// Records the error of `res`, a second error at the same position is dropped as a follow-up error
+    fn report<T>(&mut self, res: ParseResult<T>) -> Option<T> {
+        match res {
+            Ok(value) => Some(value),
+            Err(err) => {
+                let is_duplicate = self
+                    .diagnostics
+                    .last()
+                    .is_some_and(|last| (last.row, last.col) == (err.row, err.col));
+                if !is_duplicate {
+                    self.diagnostics.push(err);
+                }
+                None
+            }
+        }
+    }
+
+    /// Records the error of `res` and skips to the next token in `sync`
+    fn recover<T>(&mut self, res: ParseResult<T>, sync: &[&str]) -> Option<T> {
+        let res = self.report(res);
+        if res.is_none() {
+            self.synchronize(sync);
+        }
+        res
+    }
+
+    /// Records the error of a declaration and skips to the next section or method
+    fn recover_section<T>(&mut self, res: ParseResult<T>, start: usize) -> Option<T> {
+        let res = self.report(res);
+        if res.is_none() {
+            if self.consumed == start {
+                self.next();
+            }
+            loop {
+                self.synchronize(&SECTION_KEYWORDS);
+
+                // Only `end.` ends the unit, `class` has to start a method
+                let skip = if self.is_keyword("end") {
+                    self.peek_nth(1).typ != TokenTyp::Symbol(Symbol::Dot)
+                } else {
+                    self.is_keyword("class") && !self.is_method_start()
+                };
+                if !skip {
+                    break;
+                }
+                self.next();
+            }
+        }
+        res
+    }
+
+    /// Skips tokens until one of `sync` outside of nested blocks or the end of the file
+    fn synchronize(&mut self, sync: &[&str]) {
+        let mut depth = 0;
+
+        loop {
+            let tok = self.peek();
+            if tok.typ == TokenTyp::EOF {
+                break;
+            }
+            if matches!(
+                tok.typ,
+                TokenTyp::Keyword
+                    | TokenTyp::Directive
+                    | TokenTyp::Identifier
+                    | TokenTyp::Symbol(_)
+            ) {
+                let content = tok.content.to_lowercase();
+                if depth == 0 && sync.contains(&content.as_str()) {
+                    break;
+                }
+                if tok.typ == TokenTyp::Keyword {
+                    if BLOCK_KEYWORDS.contains(&content.as_str()) || content == "repeat" {
+                        depth += 1;
+                    } else if content == "end" || content == "until" {
+                        depth = usize::saturating_sub(depth, 1);
+                    }
+                }
+            }
+            self.next();
+        }
     }
 }
-pub struct File {
-    name: String,
-    interface: Vec<Interface>,
-    implementation: String,
-    initialization: String,
-    finalization: String,
-}
 
-pub enum Interface {
-    imports(Vec<String>),
-    constant(Vec<(String, Token)>),
-    definition,
-    variable,
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use crate::parser::*;
+
+    fn parse(content: &str) -> Unit {
+        let mut parser = Parser::new(content);
+        let file = parser.parse_unit();
+        assert_eq!(parser.diagnostics(), vec![]);
+        file
+    }
+
+    #[test]
+    fn empty_unit() {
+        let file = parse("unit Foo;\ninterface\nimplementation\nend.");
+
+        assert_eq!(file.name, "Foo");
+        assert!(file.interface.is_empty());
+        assert!(file.implementation.is_empty());
+    }
+
+    #[test]
+    fn dotted_unit_name() {
+        let file = parse("unit Foo.Bar;\ninterface\nimplementation\nend.");
+
+        assert_eq!(file.name, "Foo.Bar");
+    }
+
+    fn parse_any(content: &str) -> File {
+        let mut parser = Parser::new(content);
+        let file = parser.parse_file();
+        assert_eq!(parser.diagnostics(), vec![]);
+        file
+    }
+
+    #[test]
+    fn program() {
+        let file = parse_any(
+            "program Project1;
+
+{$APPTYPE CONSOLE}
+
+uses
+  System.SysUtils,
+  Main in 'src\\Main.pas' {MainForm};
+
+{$R *.res}
+
+var
+  X: Integer;
+
+begin
+  X := 1;
+  Writeln(X);
+end.",
+        );
+
+        let File::Program(program) = file else {
+            panic!("expected a program");
+        };
+        assert_eq!(program.name, "Project1");
+        let Implementation::Imports(imports) = &program.declarations[0] else {
+            panic!("expected imports");
+        };
+        assert_eq!(imports[1].name, "Main");
+        assert_eq!(imports[1].path, Some(String::from("src\\Main.pas")));
+        assert!(matches!(
+            program.declarations[1],
+            Implementation::Variable(_)
+        ));
+        assert_eq!(program.body.len(), 2);
+
+        // Old style program parameters
+        let file = parse_any("program Foo(Input, Output);\nbegin\nend.");
+        assert!(matches!(file, File::Program(program) if program.name == "Foo"));
+    }
+
+    #[test]
+    fn library() {
+        let file = parse_any(
+            "library Foo;
+
+uses
+  Bar in 'Bar.pas';
+
+function Add(A, B: Integer): Integer; stdcall;
+begin
+  Result := A + B;
+end;
+
+exports
+  Add,
+  Bar.Sub(A: Integer) name 'Sub1' index 2,
+  Bar.Mul resident;
+
+end.",
+        );
+
+        let File::Library(library) = file else {
+            panic!("expected a library");
+        };
+        assert_eq!(library.name, "Foo");
+        assert!(library.body.is_empty());
+        let Implementation::Exports(exports) = &library.declarations[2] else {
+            panic!("expected exports");
+        };
+        let names: Vec<&str> = exports.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Add", "Bar.Sub", "Bar.Mul"]);
+        assert_eq!(exports[0].params, None);
+        assert_eq!(exports[1].params.as_ref().unwrap().len(), 1);
+        assert_eq!(
+            exports[1].export_name,
+            Some(ExpressionKind::String(String::from("Sub1")).into())
+        );
+        assert_eq!(exports[1].index, Some(*number("2")));
+
+        let file = parse_any("library Foo;\nexports Bar;\nbegin\n  Init;\nend.");
+        assert!(matches!(file, File::Library(library) if library.body.len() == 1));
+    }
+
+    #[test]
+    fn package() {
+        let file = parse_any(
+            "package MyPackage;
+
+{$R *.res}
+{$DESCRIPTION 'My components'}
+{$RUNONLY}
+
+requires
+  rtl,
+  vcl,
+  Vcl.Imaging;
+
+contains
+  Foo in 'Foo.pas',
+  Bar.Baz in 'sub\\Bar.Baz.pas';
+
+end.",
+        );
+
+        let File::Package(package) = file else {
+            panic!("expected a package");
+        };
+        assert_eq!(package.name, "MyPackage");
+        assert_eq!(package.requires, vec!["rtl", "vcl", "Vcl.Imaging"]);
+        assert_eq!(
+            package.contains,
+            vec![
+                Import {
+                    name: String::from("Foo"),
+                    path: Some(String::from("Foo.pas")),
+                },
+                Import {
+                    name: String::from("Bar.Baz"),
+                    path: Some(String::from("sub\\Bar.Baz.pas")),
+                },
+            ]
+        );
+
+        let file = parse_any("package Empty;\nend.");
+        assert!(matches!(file, File::Package(package) if package.contains.is_empty()));
+    }
+
+    #[test]
+    fn file_errors() {
+        let mut parser = Parser::new("program Foo;\nvar X: Integer;\nend.");
+        assert!(matches!(parser.parse_file(), File::Program(_)));
+        let messages: Vec<String> = parser
+            .diagnostics()
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(messages, vec!["Expected `begin`, found `end`"]);
+
+        let mut parser = Parser::new("package Foo;\nrequires rtl vcl;\ncontains A;\nend.");
+        let File::Package(package) = parser.parse_file() else {
+            panic!("expected a package");
+        };
+        assert_eq!(parser.diagnostics().len(), 1);
+        assert_eq!(package.contains[0].name, "A");
+
+        let mut parser = Parser::new("x := 1;");
+        assert!(matches!(parser.parse_file(), File::Unit(_)));
+        assert_eq!(
+            parser.diagnostics()[0].message,
+            "Expected `unit`, found `x`"
+        );
+    }
+
+    #[test]
+    fn identifier_rules() {
+        let file = parse(
+            "UNIT My_Unit;
+INTERFACE
+USES System.SysUtils, Vcl.&Type;
+Type
+  TMy_Class = Class
+    Procedure &Begin;
+  End;
+IMPLEMENTATION
+Procedure TMy_Class.&Begin;
+BEGIN
+  Größe := _Value;
+END;
+End.",
+        );
+
+        assert_eq!(file.name, "My_Unit");
+        let Interface::Imports(imports) = &file.interface[0] else {
+            panic!("Expected imports");
+        };
+        let names: Vec<&str> = imports.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, vec!["System.SysUtils", "Vcl.Type"]);
+        let Implementation::Method(method) = &file.implementation[0] else {
+            panic!("Expected a method");
+        };
+        assert_eq!(method.head.name, "TMy_Class.Begin");
+    }
+
+    #[test]
+    fn directives_as_identifiers() {
+        parse(
+            "unit Foo;
+interface
+type
+  TFoo = class sealed
+  strict private
+    Name: string;
+    Index, Message: Integer;
+    Default: Boolean;
+  public
+    property Items[Index: Integer]: string read GetItem write SetItem; default;
+    procedure Read(Out: Integer); message WM_USER;
+    class operator Add(A, B: TFoo): TFoo; static;
+  end;
+  TProc = reference to procedure;
+  TCallback = function(Wnd: HWND): BOOL winapi;
+procedure Foo; winapi;
+procedure Bar; unsafe;
+implementation
+procedure Foo; winapi;
+begin
+end;
+procedure Bar; unsafe;
+var
+  Winapi, Unsafe: Integer;
+begin
+end;
+procedure TFoo.Read(Out: Integer);
+var
+  Platform: Integer;
+begin
+  Platform := Index + Name.Length;
+  Helper.Write(Platform);
+  try
+  except
+    on E: Exception do
+      Deprecated := True;
+  end;
+end;
+end.",
+        );
+    }
+
+    #[test]
+    fn uses() {
+        let file = parse(
+            "unit Foo;\ninterface\nuses System.SysUtils, Bar in 'Bar.pas';\nimplementation\nuses Baz;\nend.",
+        );
+
+        assert_eq!(
+            file.interface[0],
+            Interface::Imports(vec![
+                Import {
+                    name: String::from("System.SysUtils"),
+                    path: None
+                },
+                Import {
+                    name: String::from("Bar"),
+                    path: Some(String::from("Bar.pas"))
+                },
+            ])
+        );
+        assert_eq!(
+            file.implementation[0],
+            Implementation::Imports(vec![Import {
+                name: String::from("Baz"),
+                path: None
+            }])
+        );
+    }
+
+    #[test]
+    fn constants() {
+        let file = parse(
+            "unit Foo;\ninterface\nconst\n  Max = 10;\n  Name: string = 'a;b';\nimplementation\nend.",
+        );
+
+        let Interface::Constant(consts) = &file.interface[0] else {
+            panic!("expected a const section");
+        };
+        assert_eq!(consts.len(), 2);
+        assert_eq!(consts[0].name, "Max");
+        assert_eq!(
+            consts[0].value,
+            ExpressionKind::Number(String::from("10")).into()
+        );
+        assert_eq!(consts[1].typ, Some(Type::Named(String::from("string"))));
+        assert_eq!(
+            consts[1].value,
+            ExpressionKind::String(String::from("a;b")).into()
+        );
+    }
+
+    #[test]
+    fn variables() {
+        let file = parse("unit Foo;\ninterface\nvar\n  A, B: Integer;\nimplementation\nend.");
+
+        assert_eq!(
+            file.interface[0],
+            Interface::Variable(vec![Variable {
+                names: vec![String::from("A"), String::from("B")],
+                typ: Type::Named(String::from("Integer")),
+                absolute: None,
+                value: None,
+            }])
+        );
+    }
+
+    #[test]
+    fn absolute_and_hints() {
+        let file = parse(
+            "unit Foo;
+interface
+const
+  Old = 1 deprecated;
+  Older: Integer = 2 deprecated 'Use New';
+  New = 3 platform experimental;
+var
+  A: Integer;
+  B: Cardinal absolute A;
+  C: Integer library;
+  Platform: Integer = 0 platform;
+implementation
+end.",
+        );
+
+        let Interface::Constant(consts) = &file.interface[0] else {
+            panic!("expected a const section");
+        };
+        let names: Vec<&str> = consts.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Old", "Older", "New"]);
+
+        let Interface::Variable(vars) = &file.interface[1] else {
+            panic!("expected a var section");
+        };
+        assert_eq!(vars.len(), 4);
+        assert_eq!(vars[1].absolute.as_deref(), Some("A"));
+        assert_eq!(vars[2].typ, Type::Named(String::from("Integer")));
+        assert_eq!(vars[3].names, vec![String::from("Platform")]);
+        assert_eq!(vars[3].value, Some(*number("0")));
+    }
+
+    #[test]
+    fn class_definition() {
+        let file = parse(
+            "unit Foo;
+interface
+type
+  TFoo = class(TBar, IBaz)
+  private
+    FValue: Integer;
+  public
+    constructor Create(const AValue: Integer);
+    function GetValue: Integer; virtual;
+  end;
+  TForward = class;
+implementation
+end.",
+        );
+
+        let Interface::Definition(types) = &file.interface[0] else {
+            panic!("expected a type section");
+        };
+        assert_eq!(types[0].name, "TFoo");
+        let TypeDefinition::Class(class) = &types[0].definition else {
+            panic!("expected a class");
+        };
+        assert_eq!(class.kind, ClassKind::Class);
+        assert_eq!(class.ancestors, vec!["TBar", "IBaz"]);
+        assert_eq!(class.sections.len(), 2);
+        assert_eq!(class.sections[0].visibility, Some(Visibility::Private));
+        assert_eq!(class.sections[1].members.len(), 2);
+        let ClassMember::Method(method) = &class.sections[1].members[1] else {
+            panic!("expected a method");
+        };
+        assert_eq!(method.kind, MethodKind::Function);
+        assert_eq!(method.directives, vec![MethodDirective::Virtual]);
+
+        assert_eq!(
+            types[1].definition,
+            TypeDefinition::Forward(ClassKind::Class)
+        );
+    }
+
+    fn parse_types(types: &str) -> Vec<TypeDeclaration> {
+        let mut file = parse(&format!(
+            "unit Foo;\ninterface\ntype\n{types}\nimplementation\nend."
+        ));
+        let Interface::Definition(types) = file.interface.remove(0) else {
+            panic!("expected a type section");
+        };
+        types
+    }
+
+    fn parse_class(types: &str) -> Class {
+        let TypeDefinition::Class(class) = parse_types(types).remove(0).definition else {
+            panic!("expected a class");
+        };
+        class
+    }
+
+    #[test]
+    fn class_sections() {
+        let class = parse_class(
+            "TFoo = class abstract(TInterfacedObject, IFoo)
+  const
+    Max = 10;
+  strict private
+    class var FCount: Integer;
+    var
+    FName: string;
+    [Weak] FOwner: TFoo;
+  strict protected
+    procedure Changed; virtual; abstract;
+  public
+    class function Count: Integer; static; inline;
+    procedure Assign(Source: TFoo); reintroduce; overload;
+    procedure Paint; override; final;
+    function Get<T>: T;
+end;",
+        );
+
+        assert!(class.is_abstract);
+        assert_eq!(class.sections.len(), 4);
+        assert_eq!(class.sections[0].visibility, None);
+        assert!(matches!(
+            class.sections[0].members[0],
+            ClassMember::Constant(_)
+        ));
+        assert_eq!(
+            class.sections[1].visibility,
+            Some(Visibility::StrictPrivate)
+        );
+        assert!(matches!(
+            class.sections[1].members[0],
+            ClassMember::ClassVar(_)
+        ));
+        assert!(matches!(
+            class.sections[1].members[1],
+            ClassMember::Field(_)
+        ));
+        assert_eq!(class.sections[1].members.len(), 3);
+        assert_eq!(
+            class.sections[2].visibility,
+            Some(Visibility::StrictProtected)
+        );
+
+        let directives: Vec<Vec<MethodDirective>> = class.sections[3]
+            .members
+            .iter()
+            .map(|member| match member {
+                ClassMember::Method(head) => head.directives.clone(),
+                _ => panic!("expected a method"),
+            })
+            .collect();
+        assert_eq!(
+            directives,
+            vec![
+                vec![MethodDirective::Static, MethodDirective::Inline],
+                vec![MethodDirective::Reintroduce, MethodDirective::Overload],
+                vec![MethodDirective::Override, MethodDirective::Final],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn properties() {
+        let class = parse_class(
+            "TFoo = class
+  published
+    property Name: string read FName write SetName stored False default 'x';
+    property Items[Index: Integer]: TItem read GetItem; default;
+    property Left: Integer index 0 read GetCoord nodefault;
+    property Tag;
+    property Intf: IFoo read FIntf implements IFoo;
+end;",
+        );
+
+        let properties: Vec<&Property> = class.sections[0]
+            .members
+            .iter()
+            .map(|member| match member {
+                ClassMember::Property(property) => &**property,
+                _ => panic!("expected a property"),
+            })
+            .collect();
+
+        assert_eq!(properties[0].read, Some(String::from("FName")));
+        assert_eq!(properties[0].write, Some(String::from("SetName")));
+        assert_eq!(
+            properties[0].stored,
+            Some(ExpressionKind::Identifier(String::from("False")).into())
+        );
+        assert_eq!(
+            properties[0].default,
+            Some(ExpressionKind::String(String::from("x")).into())
+        );
+        assert_eq!(properties[1].params.len(), 1);
+        assert!(properties[1].is_default);
+        assert!(properties[2].index.is_some());
+        assert!(properties[2].nodefault);
+        assert_eq!(properties[3].typ, None);
+        assert_eq!(properties[4].implements, vec!["IFoo"]);
+    }
+
+    #[test]
+    fn interfaces_and_helpers() {
+        let types = parse_types(
+            "IFoo = interface(IInterface)
+  ['{8F6E7B3A-1234-4C5D-9E8F-0123456789AB}']
+  function GetValue: Integer;
+end;
+TFooHelper = class helper for TFoo
+end;
+TPointHelper = record helper for TPoint
+end;",
+        );
+
+        let TypeDefinition::Class(intf) = &types[0].definition else {
+            panic!("expected an interface");
+        };
+        assert_eq!(intf.kind, ClassKind::Interface);
+        assert_eq!(
+            intf.guid,
+            Some(String::from("{8F6E7B3A-1234-4C5D-9E8F-0123456789AB}"))
+        );
+
+        let TypeDefinition::Class(helper) = &types[1].definition else {
+            panic!("expected a helper");
+        };
+        assert_eq!(helper.kind, ClassKind::ClassHelper);
+        assert_eq!(helper.helper_for, Some(String::from("TFoo")));
+
+        let TypeDefinition::Class(helper) = &types[2].definition else {
+            panic!("expected a helper");
+        };
+        assert_eq!(helper.kind, ClassKind::RecordHelper);
+    }
+
+    #[test]
+    fn attribute_of_first_member() {
+        let class = parse_class(
+            "TFoo = class
+  [Weak] FOwner: TObject;
+end;",
+        );
+
+        assert_eq!(class.guid, None);
+        assert!(matches!(
+            &class.sections[0].members[0],
+            ClassMember::Field(field) if field.names == vec!["FOwner"]
+        ));
+
+        let types = parse_types(
+            "TBar = record
+  [Stored(False)] X: Integer;
+end;",
+        );
+        let TypeDefinition::Class(record) = &types[0].definition else {
+            panic!("expected a record");
+        };
+        assert_eq!(record.guid, None);
+        assert_eq!(record.sections[0].members.len(), 1);
+    }
+
+    #[test]
+    fn records() {
+        let class = parse_class(
+            "TShape = packed record
+  Name: string[20];
+  class operator Add(A, B: TShape): TShape;
+  case Kind: TShapeKind of
+    skRect: (Width, Height: Integer);
+    skCircle: (Radius: Integer;
+      case Boolean of
+        True: (Filled: Boolean));
+end;",
+        );
+
+        assert_eq!(class.kind, ClassKind::Record);
+        assert!(class.packed);
+        assert_eq!(class.sections[0].members.len(), 2);
+        let ClassMember::Method(operator) = &class.sections[0].members[1] else {
+            panic!("expected an operator");
+        };
+        assert_eq!(operator.kind, MethodKind::Operator);
+
+        let variant = class.variant_part.unwrap();
+        assert_eq!(variant.tag, Some(String::from("Kind")));
+        assert_eq!(variant.branches.len(), 2);
+        assert_eq!(variant.branches[0].fields[0].names, vec!["Width", "Height"]);
+        assert!(variant.branches[1].variant_part.is_some());
+    }
+
+    #[test]
+    fn type_aliases() {
+        let types = parse_types(
+            "TColor = (clRed, clGreen = 5);
+TColors = set of TColor;
+TDigit = 0..9;
+TMatrix = array[0..2, TColor] of Double;
+TBytes = array of Byte;
+PInteger = ^Integer;
+TFooClass = class of TFoo;
+TNotify = procedure(Sender: TObject) of object;
+TCallback = procedure(Code: Integer); stdcall;
+TFunc = reference to function: Integer;
+TList<T: class> = TObjectList<T>;",
+        );
+
+        let definitions: Vec<&TypeDefinition> = types.iter().map(|t| &t.definition).collect();
+        let TypeDefinition::Alias(Type::Enum(values)) = definitions[0] else {
+            panic!("expected an enum");
+        };
+        assert_eq!(values[1].value, Some(*number("5")));
+        assert!(matches!(
+            definitions[1],
+            TypeDefinition::Alias(Type::Set(_))
+        ));
+        assert!(matches!(
+            definitions[2],
+            TypeDefinition::Alias(Type::Subrange(_, _))
+        ));
+        let TypeDefinition::Alias(Type::Array { dimensions, .. }) = definitions[3] else {
+            panic!("expected an array");
+        };
+        assert_eq!(dimensions.len(), 2);
+        assert_eq!(
+            *definitions[4],
+            TypeDefinition::Alias(Type::Array {
+                dimensions: vec![],
+                element: Box::new(Type::Named(String::from("Byte"))),
+            })
+        );
+        assert!(matches!(
+            definitions[5],
+            TypeDefinition::Alias(Type::Pointer(_))
+        ));
+        assert_eq!(
+            *definitions[6],
+            TypeDefinition::Alias(Type::ClassOf(String::from("TFoo")))
+        );
+        let TypeDefinition::Alias(Type::Procedure(notify)) = definitions[7] else {
+            panic!("expected a procedural type");
+        };
+        assert!(notify.of_object);
+        assert!(matches!(
+            definitions[8],
+            TypeDefinition::Alias(Type::Procedure(_))
+        ));
+        let TypeDefinition::Alias(Type::Procedure(func)) = definitions[9] else {
+            panic!("expected a procedural type");
+        };
+        assert!(func.is_reference);
+        assert_eq!(func.kind, MethodKind::Function);
+
+        assert_eq!(types[10].generic_params[0].constraints, vec!["class"]);
+        assert_eq!(
+            *definitions[10],
+            TypeDefinition::Alias(Type::Generic {
+                name: String::from("TObjectList"),
+                args: vec![Type::Named(String::from("T"))],
+            })
+        );
+    }
+
+    #[test]
+    fn subrange_bounds() {
+        let types = parse_types(
+            "TInt = Low(Integer)..High(Integer);
+TIndex = 0..SizeOf(Pointer) * 8 - 1;
+TSmall = Min..Max;
+TRange = System.Low(Byte)..Pred(Max);
+TField = record
+  A: Integer;
+  B: Ord('A')..Ord('Z');
+end;",
+        );
+
+        let TypeDefinition::Alias(Type::Subrange(start, end)) = &types[0].definition else {
+            panic!("expected a subrange");
+        };
+        assert_eq!(
+            start.kind,
+            ExpressionKind::Call {
+                callee: ident("Low"),
+                args: vec![*ident("Integer")],
+            }
+        );
+        assert!(matches!(&end.kind, ExpressionKind::Call { .. }));
+        for typ in &types[1..4] {
+            assert!(matches!(
+                typ.definition,
+                TypeDefinition::Alias(Type::Subrange(_, _))
+            ));
+        }
+        let TypeDefinition::Alias(Type::Subrange(start, _)) = &types[3].definition else {
+            panic!("expected a subrange");
+        };
+        assert!(matches!(&start.kind, ExpressionKind::Call { .. }));
+    }
+
+    #[test]
+    fn method_head() {
+        let file = parse(
+            "unit Foo;\ninterface\nprocedure Bar(var A: Integer; B, C: string = 'x'; const D);\nimplementation\nend.",
+        );
+
+        let Interface::Method(head) = &file.interface[0] else {
+            panic!("expected a method head");
+        };
+        assert_eq!(head.kind, MethodKind::Procedure);
+        assert_eq!(head.name, "Bar");
+        assert_eq!(head.params.len(), 3);
+        assert_eq!(head.params[0].modifier, Some(ParameterModifier::Var));
+        assert_eq!(head.params[1].names, vec!["B", "C"]);
+        assert!(head.params[1].default.is_some());
+        assert_eq!(head.params[2].typ, None);
+    }
+
+    #[test]
+    fn method_implementation() {
+        let file = parse(
+            "unit Foo;
+interface
+implementation
+function TFoo.Bar(A: Integer): Integer;
+var
+  I: Integer;
+begin
+  try
+    I := A;
+  finally
+    Result := I;
+  end;
+end;
+procedure Ext; external 'foo.dll';
+end.",
+        );
+
+        let Implementation::Method(method) = &file.implementation[0] else {
+            panic!("expected a method");
+        };
+        assert_eq!(method.head.name, "TFoo.Bar");
+        assert_eq!(method.declarations.len(), 1);
+        let body = method.body.as_ref().unwrap();
+        assert_eq!(body.len(), 1);
+        assert!(matches!(
+            body[0].kind,
+            StatementKind::Try {
+                handler: TryHandler::Finally(_),
+                ..
+            }
+        ));
+
+        let Implementation::Method(method) = &file.implementation[1] else {
+            panic!("expected a method");
+        };
+        assert_eq!(method.body, None);
+    }
+
+    #[test]
+    fn initialization_finalization() {
+        let file = parse(
+            "unit Foo;\ninterface\nimplementation\ninitialization\n  Bar;\nfinalization\n  Baz;\nend.",
+        );
+
+        assert_eq!(
+            file.initialization,
+            vec![StatementKind::Call(*ident("Bar")).into()]
+        );
+        assert_eq!(
+            file.finalization,
+            vec![StatementKind::Call(*ident("Baz")).into()]
+        );
+    }
+
+    fn parse_body(body: &str) -> Vec<StatementKind> {
+        let file = parse(&format!(
+            "unit Foo;\ninterface\nimplementation\nprocedure Bar;\nbegin\n{body}\nend;\nend."
+        ));
+        let Implementation::Method(method) = &file.implementation[0] else {
+            panic!("expected a method");
+        };
+        let body = method.body.clone().unwrap();
+        body.into_iter().map(|statement| statement.kind).collect()
+    }
+
+    fn parse_expression(content: &str) -> ExpressionKind {
+        Parser::new(content).parse_expression().unwrap().kind
+    }
+
+    fn ident(name: &str) -> Box<Expression> {
+        Box::new(ExpressionKind::Identifier(String::from(name)).into())
+    }
+
+    fn number(value: &str) -> Box<Expression> {
+        Box::new(ExpressionKind::Number(String::from(value)).into())
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(
+            parse_expression("1 + 2 * 3"),
+            ExpressionKind::Binary {
+                op: BinaryOp::Add,
+                left: number("1"),
+                right: Box::new(
+                    ExpressionKind::Binary {
+                        op: BinaryOp::Multiply,
+                        left: number("2"),
+                        right: number("3"),
+                    }
+                    .into()
+                ),
+            }
+        );
+
+        assert_eq!(
+            parse_expression("A or B and C"),
+            ExpressionKind::Binary {
+                op: BinaryOp::Or,
+                left: ident("A"),
+                right: Box::new(
+                    ExpressionKind::Binary {
+                        op: BinaryOp::And,
+                        left: ident("B"),
+                        right: ident("C"),
+                    }
+                    .into()
+                ),
+            }
+        );
+
+        assert_eq!(
+            parse_expression("not A = B"),
+            ExpressionKind::Binary {
+                op: BinaryOp::Equal,
+                left: Box::new(
+                    ExpressionKind::Unary {
+                        op: UnaryOp::Not,
+                        operand: ident("A"),
+                    }
+                    .into()
+                ),
+                right: ident("B"),
+            }
+        );
+
+        assert_eq!(
+            parse_expression("A shl 2 div B"),
+            ExpressionKind::Binary {
+                op: BinaryOp::Div,
+                left: Box::new(
+                    ExpressionKind::Binary {
+                        op: BinaryOp::Shl,
+                        left: ident("A"),
+                        right: number("2"),
+                    }
+                    .into()
+                ),
+                right: ident("B"),
+            }
+        );
+    }
+
+    #[test]
+    fn relational_keywords() {
+        assert_eq!(
+            parse_expression("A in [B, C..D]"),
+            ExpressionKind::Binary {
+                op: BinaryOp::In,
+                left: ident("A"),
+                right: Box::new(
+                    ExpressionKind::Set(vec![
+                        *ident("B"),
+                        ExpressionKind::Range(ident("C"), ident("D")).into(),
+                    ])
+                    .into()
+                ),
+            }
+        );
+
+        assert_eq!(
+            parse_expression("(A as TFoo) is TBar"),
+            ExpressionKind::Binary {
+                op: BinaryOp::Is,
+                left: Box::new(
+                    ExpressionKind::Binary {
+                        op: BinaryOp::As,
+                        left: ident("A"),
+                        right: ident("TFoo"),
+                    }
+                    .into()
+                ),
+                right: ident("TBar"),
+            }
+        );
+    }
+
+    #[test]
+    fn postfix_expressions() {
+        assert_eq!(
+            parse_expression("@P^.Items[I].Free()"),
+            ExpressionKind::Unary {
+                op: UnaryOp::AddressOf,
+                operand: Box::new(
+                    ExpressionKind::Call {
+                        callee: Box::new(
+                            ExpressionKind::Member {
+                                object: Box::new(
+                                    ExpressionKind::Index {
+                                        object: Box::new(
+                                            ExpressionKind::Member {
+                                                object: Box::new(
+                                                    ExpressionKind::Dereference(ident("P")).into()
+                                                ),
+                                                name: String::from("Items"),
+                                            }
+                                            .into()
+                                        ),
+                                        indices: vec![*ident("I")],
+                                    }
+                                    .into()
+                                ),
+                                name: String::from("Free"),
+                            }
+                            .into()
+                        ),
+                        args: vec![],
+                    }
+                    .into()
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn generic_instantiation() {
+        assert_eq!(
+            parse_expression("TList<Integer>.Create"),
+            ExpressionKind::Member {
+                object: Box::new(
+                    ExpressionKind::Generic {
+                        object: ident("TList"),
+                        args: vec![Type::Named(String::from("Integer"))],
+                    }
+                    .into()
+                ),
+                name: String::from("Create"),
+            }
+        );
+        assert!(matches!(
+            parse_expression("Generics.TDictionary<string, TList<T>>.Create(10)"),
+            ExpressionKind::Call { callee, .. } if matches!(
+                &callee.kind,
+                ExpressionKind::Member { object, .. } if matches!(
+                    &object.kind,
+                    ExpressionKind::Generic { args, .. } if args.len() == 2
+                )
+            )
+        ));
+        assert!(matches!(
+            parse_expression("Default<T>(X)"),
+            ExpressionKind::Call { callee, .. } if matches!(callee.kind, ExpressionKind::Generic { .. })
+        ));
+
+        // Comparisons stay comparisons
+        assert!(matches!(
+            parse_expression("A < B"),
+            ExpressionKind::Binary {
+                op: BinaryOp::Less,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse_expression("(A < B) and (C > D)"),
+            ExpressionKind::Binary {
+                op: BinaryOp::And,
+                ..
+            }
+        ));
+
+        let body = parse_body("L := TList<Integer>.Create;\nL.Free;");
+        assert!(matches!(&body[0], StatementKind::Assignment { value, .. }
+            if matches!(value.kind, ExpressionKind::Member { .. })));
+    }
+
+    #[test]
+    fn alternative_brackets() {
+        assert_eq!(parse_expression("A(. I .)"), parse_expression("A[I]"));
+        assert_eq!(parse_expression("(.1, 2 .)"), parse_expression("[1, 2]"));
+        assert_eq!(
+            parse_expression("@@Proc"),
+            ExpressionKind::Unary {
+                op: UnaryOp::AddressOf,
+                operand: ident("Proc"),
+            }
+        );
+    }
+
+    #[test]
+    fn typed_constants() {
+        assert_eq!(
+            parse_expression("(1, 2)"),
+            ExpressionKind::ArrayConstant(vec![*number("1"), *number("2")])
+        );
+        assert_eq!(
+            parse_expression("(X: 1; Y: 2)"),
+            ExpressionKind::RecordConstant(vec![
+                (String::from("X"), *number("1")),
+                (String::from("Y"), *number("2")),
+            ])
+        );
+    }
+
+    #[test]
+    fn assignment_and_call() {
+        let body = parse_body("A := B;\nFoo(A, 1);\n;");
+
+        assert_eq!(
+            body,
+            vec![
+                StatementKind::Assignment {
+                    target: *ident("A"),
+                    value: *ident("B"),
+                },
+                StatementKind::Call(
+                    ExpressionKind::Call {
+                        callee: ident("Foo"),
+                        args: vec![*ident("A"), *number("1")],
+                    }
+                    .into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let file = parse(
+            "unit Foo;\ninterface\nimplementation\ninitialization\n  X := (A + B) * C;\n  if X then\n    Foo(1);\nend.",
+        );
+        let columns = |span: Span| (span.start.row, span.start.col, span.end.row, span.end.col);
+
+        let StatementKind::Assignment { target, value } = &file.initialization[0].kind else {
+            panic!("expected an assignment");
+        };
+        assert_eq!(columns(file.initialization[0].span), (5, 3, 5, 19));
+        assert_eq!(columns(target.span), (5, 3, 5, 4));
+        assert_eq!(columns(value.span), (5, 8, 5, 19));
+        let ExpressionKind::Binary { left, .. } = &value.kind else {
+            panic!("expected a binary expression");
+        };
+        assert_eq!(columns(left.span), (5, 8, 5, 15));
+
+        let StatementKind::If { then_branch, .. } = &file.initialization[1].kind else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(columns(file.initialization[1].span), (6, 3, 7, 11));
+        assert_eq!(columns(then_branch.as_ref().unwrap().span), (7, 5, 7, 11));
+    }
+
+    #[test]
+    fn if_statement() {
+        let body = parse_body("if A then\n  B\nelse if C then\n  D;");
+
+        let StatementKind::If {
+            condition,
+            then_branch,
+            else_branch,
+        } = &body[0]
+        else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(*condition, *ident("A"));
+        assert_eq!(
+            then_branch.as_deref(),
+            Some(&StatementKind::Call(*ident("B")).into())
+        );
+        assert!(matches!(
+            else_branch.as_ref().map(|s| &s.kind),
+            Some(StatementKind::If { .. })
+        ));
+    }
+
+    #[test]
+    fn case_statement() {
+        let body = parse_body("case A of\n  1, 2..5: B;\n  6: ;\nelse\n  C;\n  D;\nend;");
+
+        let StatementKind::Case {
+            selector,
+            branches,
+            else_branch,
+        } = &body[0]
+        else {
+            panic!("expected a case statement");
+        };
+        assert_eq!(*selector, *ident("A"));
+        assert_eq!(branches.len(), 2);
+        assert_eq!(
+            branches[0].labels,
+            vec![
+                *number("1"),
+                ExpressionKind::Range(number("2"), number("5")).into()
+            ]
+        );
+        assert_eq!(branches[1].body, None);
+        assert_eq!(else_branch.as_ref().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn loops() {
+        let body = parse_body(
+            "for I := 10 downto 1 do\n  Foo(I);
+for var S in List do\n  Bar(S);
+for var J: Integer := 0 to 5 do\n  Baz(J);
+for var T: TPair<string, Integer> in Map do\n  ;
+while A do\n  begin\n    Break;\n  end;
+repeat\n  Continue;\nuntil B;",
+        );
+
+        assert!(matches!(body[0], StatementKind::For { downto: true, .. }));
+        assert!(matches!(
+            body[1],
+            StatementKind::ForIn {
+                inline_var: true,
+                typ: None,
+                ..
+            }
+        ));
+        let StatementKind::For { typ, end, .. } = &body[2] else {
+            panic!("expected a for loop");
+        };
+        assert_eq!(typ.as_deref(), Some(&Type::Named(String::from("Integer"))));
+        assert_eq!(*end, *number("5"));
+        assert!(matches!(
+            &body[3],
+            StatementKind::ForIn { typ: Some(typ), .. } if matches!(**typ, Type::Generic { .. })
+        ));
+        assert_eq!(
+            body[4],
+            StatementKind::While {
+                condition: *ident("A"),
+                body: Some(Box::new(
+                    StatementKind::Compound(vec![StatementKind::Break.into()]).into()
+                )),
+            }
+        );
+        assert_eq!(
+            body[5],
+            StatementKind::Repeat {
+                body: vec![StatementKind::Continue.into()],
+                condition: *ident("B"),
+            }
+        );
+    }
+
+    #[test]
+    fn try_except() {
+        let body = parse_body(
+            "try\n  Foo;\nexcept\n  on E: EFoo do\n    raise;\n  on EBar do ;\nelse\n  raise Exception.Create('x') at Addr;\nend;
+try\nexcept\nend;",
+        );
+
+        let StatementKind::Try {
+            handler: TryHandler::Except(except),
+            ..
+        } = &body[0]
+        else {
+            panic!("expected a try except");
+        };
+        assert_eq!(except.handlers.len(), 2);
+        assert_eq!(except.handlers[0].variable, Some(String::from("E")));
+        assert_eq!(except.handlers[1].typ, "EBar");
+        assert!(matches!(
+            except.statements[0].kind,
+            StatementKind::Raise {
+                exception: Some(_),
+                at: Some(_)
+            }
+        ));
+
+        assert_eq!(
+            body[1],
+            StatementKind::Try {
+                body: vec![],
+                handler: TryHandler::Except(ExceptBlock {
+                    handlers: vec![],
+                    statements: vec![],
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn misc_statements() {
+        let body = parse_body(
+            "with A, B do\n  C := 1;\ninherited;\ninherited Create(A);\nExit(5);\nvar X: Integer := 1;\nconst Y = 2;",
+        );
+
+        assert!(matches!(&body[0], StatementKind::With { objects, .. } if objects.len() == 2));
+        assert_eq!(
+            body[1],
+            StatementKind::Call(ExpressionKind::Inherited(None).into())
+        );
+        assert_eq!(
+            body[2],
+            StatementKind::Call(
+                ExpressionKind::Inherited(Some(Box::new(
+                    ExpressionKind::Call {
+                        callee: ident("Create"),
+                        args: vec![*ident("A")],
+                    }
+                    .into()
+                )))
+                .into()
+            )
+        );
+        assert_eq!(body[3], StatementKind::Exit(Some(*number("5"))));
+        assert!(matches!(body[4], StatementKind::Variable { .. }));
+        assert!(matches!(body[5], StatementKind::Constant { .. }));
+    }
+
+    #[test]
+    fn asm_block() {
+        let body = parse_body("asm\n  @@loop: dec ecx; jnz @@loop // don't\nend;\nX := 1;");
+
+        let StatementKind::Asm(block) = &body[0] else {
+            panic!("expected an asm block");
+        };
+        assert_eq!((block.row, block.col), (6, 1));
+        let contents: Vec<&str> = block.tokens.iter().map(|t| &t.content[..]).collect();
+        assert_eq!(
+            contents,
+            vec!["@@loop", ":", "dec", "ecx", ";", "jnz", "@@loop"]
+        );
+        assert!(matches!(body[1], StatementKind::Assignment { .. }));
+
+        let file = parse(
+            "unit Foo;\ninterface\nimplementation\nfunction Bar: Integer;\nasm\n  MOV EAX, 0FFh\nend;\nend.",
+        );
+        let Implementation::Method(method) = &file.implementation[0] else {
+            panic!("expected a method");
+        };
+        assert!(
+            matches!(&method.body.as_ref().unwrap()[0].kind, StatementKind::Asm(block) if block.tokens.len() == 4)
+        );
+    }
+
+    #[test]
+    fn anonymous_method() {
+        let body = parse_body("Queue(procedure\n  begin\n    Foo;\n  end);");
+
+        let StatementKind::Call(call) = &body[0] else {
+            panic!("expected a call");
+        };
+        let ExpressionKind::Call { args, .. } = &call.kind else {
+            panic!("expected a call");
+        };
+        let ExpressionKind::AnonymousMethod(method) = &args[0].kind else {
+            panic!("expected an anonymous method");
+        };
+        assert_eq!(method.body, vec![StatementKind::Call(*ident("Foo")).into()]);
+    }
+
+    #[test]
+    fn syntax_error() {
+        let (_, diagnostics) = parse_with_errors("unit Foo;\nimplementation\nend.");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].row, 2);
+        assert_eq!(diagnostics[0].col, 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Expected `interface`, found `implementation`"
+        );
+    }
+
+    #[test]
+    fn text_after_end() {
+        let (file, diagnostics) = parse_with_errors(
+            "unit Foo;\ninterface\nimplementation\nend.\nNotes: don't change { this\nend",
+        );
+
+        assert_eq!(file.name, "Foo");
+        assert!(diagnostics.is_empty());
+
+        let mut parser = Parser::new("program Foo;\nbegin\nend.\n$%& 'x");
+        assert!(matches!(parser.parse_file(), File::Program(_)));
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    fn parse_with_errors(content: &str) -> (Unit, Vec<Diagnostic>) {
+        let mut parser = Parser::new(content);
+        let file = parser.parse_unit();
+        (file, parser.diagnostics())
+    }
+
+    #[test]
+    fn conditional_compilation() {
+        let content = "unit Foo;
+interface
+{$IFDEF MSWINDOWS}
+uses Windows;
+{$ELSE}
+uses Posix.Unistd;
+{$ENDIF}
+implementation
+procedure Bar;
+begin
+  {$IFDEF DEBUG}
+  Log('a');
+  {$ENDIF}
+end;
+end.";
+
+        let mut parser = Parser::with_defines(content, Defines::new(&["MSWINDOWS"]));
+        let file = parser.parse_unit();
+        assert_eq!(parser.diagnostics(), vec![]);
+        let Interface::Imports(imports) = &file.interface[0] else {
+            panic!("expected a uses clause");
+        };
+        assert_eq!(imports[0].name, "Windows");
+        let Implementation::Method(method) = &file.implementation[0] else {
+            panic!("expected a method");
+        };
+        assert!(method.body.as_ref().unwrap().is_empty());
+
+        let file = parse(content);
+        let Interface::Imports(imports) = &file.interface[0] else {
+            panic!("expected a uses clause");
+        };
+        assert_eq!(imports[0].name, "Posix.Unistd");
+    }
+
+    #[test]
+    fn multiple_errors() {
+        let (file, diagnostics) = parse_with_errors(
+            "unit Foo;
+interface
+const
+  A = ;
+  B = 2;
+var
+  C: ;
+implementation
+procedure Bar;
+begin
+  X := ;
+  Y := 1 Z := 2;
+  if A then B; else C;
+end;
+procedure Baz;
+begin
+end;
+end.",
+        );
+
+        let positions: Vec<(usize, usize)> = diagnostics.iter().map(|d| (d.row, d.col)).collect();
+        assert_eq!(positions, vec![(4, 7), (7, 6), (11, 8), (12, 10), (13, 16)]);
+
+        let Interface::Constant(consts) = &file.interface[0] else {
+            panic!("expected a const section");
+        };
+        assert_eq!(consts.len(), 1);
+        assert_eq!(consts[0].name, "B");
+
+        assert_eq!(file.implementation.len(), 2);
+        let Implementation::Method(method) = &file.implementation[0] else {
+            panic!("expected a method");
+        };
+        assert_eq!(method.body.as_ref().unwrap().len(), 3);
+        let Implementation::Method(method) = &file.implementation[1] else {
+            panic!("expected a method");
+        };
+        assert_eq!(method.head.name, "Baz");
+    }
+
+    #[test]
+    fn class_member_recovery() {
+        let (file, diagnostics) = parse_with_errors(
+            "unit Foo;
+interface
+type
+  TFoo = class
+  private
+    FValue: ;
+    42;
+  public
+    procedure Bar;
+  end;
+  TBaz = Integer;
+implementation
+end.",
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+        let Interface::Definition(types) = &file.interface[0] else {
+            panic!("expected a type section");
+        };
+        assert_eq!(types.len(), 2);
+        let TypeDefinition::Class(class) = &types[0].definition else {
+            panic!("expected a class");
+        };
+        assert_eq!(class.sections[1].visibility, Some(Visibility::Public));
+        assert_eq!(class.sections[1].members.len(), 1);
+    }
+
+    #[test]
+    fn junk_between_declarations() {
+        let (file, diagnostics) = parse_with_errors(
+            "unit Foo;
+interface
+procedure A;
+) ] 42
+procedure B;
+implementation
+end;
+procedure A;
+begin
+end;
+end.",
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!((diagnostics[0].row, diagnostics[0].col), (4, 1));
+        assert_eq!((diagnostics[1].row, diagnostics[1].col), (7, 1));
+        assert_eq!(file.interface.len(), 2);
+        assert_eq!(file.implementation.len(), 1);
+    }
+
+    #[test]
+    fn unknown_characters() {
+        let (file, diagnostics) =
+            parse_with_errors("unit Foo;\ninterface\nconst A = 1 ? ;\nimplementation\nend.");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Unexpected character `?`");
+        assert_eq!(file.interface.len(), 1);
+    }
+
+    #[test]
+    fn deep_nesting() {
+        let depth = 10_000;
+        let content = format!(
+            "unit Foo;\ninterface\nconst A = {}1{};\nimplementation\nend.",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        );
+        let (_, diagnostics) = parse_with_errors(&content);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.starts_with("Nesting too deep"));
+
+        let content = format!(
+            "unit Foo;\ninterface\nimplementation\nbegin\n{}end.",
+            "begin ".repeat(depth) + &"end; ".repeat(depth)
+        );
+        let (_, diagnostics) = parse_with_errors(&content);
+        assert!(!diagnostics.is_empty());
+    }
+
+    const SAMPLE: &str = "unit Foo.Bar;
+interface
+uses System.SysUtils;
+type
+  TFoo<T: class> = class(TInterfacedObject, IFoo)
+  strict private
+    FValue: array[0..9] of T;
+  public
+    constructor Create(const A: Integer = 0); overload;
+    property Value[I: Integer]: T read GetValue write SetValue; default;
+  end;
+  TRec = packed record
+    case Kind: Byte of
+      0: (I: Integer);
+      1: (S: string[10]);
+  end;
+implementation
+{$R *.dfm}
+function Sum(const A: array of Integer): Integer;
+var
+  I: Integer;
+begin
+  Result := 0;
+  for I in A do
+    Inc(Result, I);
+  try
+    case Result of
+      1..2: Exit;
+    else
+      raise Exception.Create('x');
+    end;
+  except
+    on E: Exception do Writeln(E.Message);
+  end;
+  repeat Dec(Result) until Result < 0;
+  asm mov eax, 1 end;
+end;
+initialization
+  TFoo.Create;
+end.";
+
+    #[test]
+    fn prefixes_do_not_panic() {
+        for (i, _) in SAMPLE.char_indices() {
+            parse_with_errors(&SAMPLE[..i]);
+        }
+        let (_, diagnostics) = parse_with_errors(SAMPLE);
+        assert_eq!(diagnostics, vec![]);
+    }
+
+    #[test]
+    fn truncated_generics() {
+        let source = "unit Foo;
+interface
+type
+  TFoo = class(TBar<Integer, TList<string>>, IFoo<T>)
+  end;
+  THelper = class helper for TBaz<Byte>
+  end;
+  TMeta = class of TFoo<Integer>;
+implementation
+end.";
+        for (i, _) in source.char_indices() {
+            parse_with_errors(&source[..i]);
+        }
+        let (_, diagnostics) = parse_with_errors(source);
+        assert_eq!(diagnostics, vec![]);
+
+        let (_, diagnostics) =
+            parse_with_errors("unit Foo;\ninterface\ntype\n  TFoo = class(TBar<Integer");
+        assert_eq!(diagnostics[0].message, "Expected `>`, found end of file");
+    }
+
+    #[test]
+    fn random_input_does_not_panic() {
+        let tokens: Vec<&str> = SAMPLE
+            .split_whitespace()
+            .chain([
+                "(", ")", "[", "]", ";", "end", "begin", "'", "{", "(*", "#", "$",
+            ])
+            .collect();
+
+        // Small linear congruential generator, so failures are reproducible
+        let mut seed: u64 = 42;
+        for _ in 0..200 {
+            let mut content = String::new();
+            for _ in 0..100 {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                content.push_str(tokens[(seed >> 33) as usize % tokens.len()]);
+                content.push(' ');
+            }
+            parse_with_errors(&content);
+        }
+    }
+}
@@ -0,0 +1,673 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{Lexer, Number, Symbol, Token, TokenTyp};
+
+/// Code of the warning about a constant in `{$IF}` without a value
+pub const UNKNOWN_CONSTANT: &str = "unknown-constant";
+
+/// Code of the warning about a valid `{$IF}` that can not be evaluated, e.g. because it calls
+/// a function other than `SizeOf`, `High`, `Low` and `Ord`
+pub const UNSUPPORTED_CONDITION: &str = "unsupported-condition";
+
+/// Conditional symbols like `DEBUG` or `MSWINDOWS` and constants like `CompilerVersion`
+/// that `{$IF}` can compare. Names are case-insensitive like in Delphi
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Defines {
+    symbols: HashSet<String>,
+    values: HashMap<String, f64>,
+}
+
+impl Defines {
+    pub fn new<S: AsRef<str>>(symbols: &[S]) -> Self {
+        let mut defines = Defines::default();
+        for symbol in symbols {
+            defines.define(symbol.as_ref());
+        }
+        defines
+    }
+
+    pub fn define(&mut self, symbol: &str) {
+        self.symbols.insert(symbol.to_uppercase());
+    }
+
+    pub fn undefine(&mut self, symbol: &str) {
+        self.symbols.remove(&symbol.to_uppercase());
+    }
+
+    pub fn is_defined(&self, symbol: &str) -> bool {
+        self.symbols.contains(&symbol.to_uppercase())
+    }
+
+    /// Sets a constant for `{$IF CompilerVersion >= 35}`
+    pub fn set_value(&mut self, name: &str, value: f64) {
+        self.values.insert(name.to_uppercase(), value);
+    }
+
+    pub fn value(&self, name: &str) -> Option<f64> {
+        self.values.get(&name.to_uppercase()).copied()
+    }
+
+    /// Adds the symbols and values of `other`
+    pub fn extend(&mut self, other: &Defines) {
+        self.symbols.extend(other.symbols.iter().cloned());
+        self.values.extend(
+            other
+                .values
+                .iter()
+                .map(|(name, value)| (name.clone(), *value)),
+        );
+    }
+
+    /// The defined symbols in uppercase and sorted
+    pub fn symbols(&self) -> Vec<String> {
+        let mut res: Vec<String> = self.symbols.iter().cloned().collect();
+        res.sort();
+        res
+    }
+}
+
+/// State of one `{$IF...}` ... `{$ENDIF}` block
+struct Branch<'a> {
+    /// The opening directive, for the error about a missing `{$ENDIF}`
+    directive: Token<'a>,
+    parent_active: bool,
+    active: bool,
+    /// A branch of this block was already active, so `{$ELSE}` is inactive
+    taken: bool,
+}
+
+/// Sits between the lexer and the parser and drops the tokens of inactive conditional branches
+pub struct Preprocessor<'a> {
+    lex: Lexer<'a>,
+    defines: Defines,
+    stack: Vec<Branch<'a>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(content: &'a str, defines: Defines) -> Self {
+        Preprocessor {
+            lex: Lexer::new(content),
+            defines,
+            stack: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Errors of the lexer and the directives found so far
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut res = self.lex.diagnostics().to_vec();
+        res.extend(self.diagnostics.iter().cloned());
+        res.sort_by_key(|d| (d.row, d.col));
+        res
+    }
+
+    /// The next token in an active branch, the conditional directives themselves are returned
+    /// too unless they are inside an inactive branch
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Token<'a> {
+        loop {
+            let tok = self.lex.next_token();
+
+            if tok.typ == TokenTyp::EOF {
+                for branch in std::mem::take(&mut self.stack) {
+                    let message = format!(
+                        "Missing `{{$ENDIF}}` for `{{${}}}`",
+                        branch.directive.content
+                    );
+                    self.diagnostics
+                        .push(Diagnostic::syntax_error(&branch.directive, message));
+                }
+                return tok;
+            }
+
+            let was_active = self.is_active();
+            if tok.typ == TokenTyp::ConditionalCompilation {
+                self.process_directive(&tok);
+            }
+            if was_active || self.is_active() {
+                return tok;
+            }
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.stack.last().is_none_or(|branch| branch.active)
+    }
+
+    fn process_directive(&mut self, tok: &Token<'a>) {
+        let content = tok.content.trim();
+        let (name, arg) = content
+            .split_once(char::is_whitespace)
+            .unwrap_or((content, ""));
+        let arg = arg.trim();
+        // Only the first word is the symbol, everything after it is a comment
+        let symbol = arg.split_whitespace().next().unwrap_or_default();
+
+        let is_active = self.is_active();
+        match name.to_uppercase().as_str() {
+            "IFDEF" | "IFNDEF" | "IF" | "IFOPT" => {
+                let active = is_active
+                    && match name.to_uppercase().as_str() {
+                        "IFDEF" => self.defines.is_defined(symbol),
+                        "IFNDEF" => !self.defines.is_defined(symbol),
+                        "IF" => self.evaluate(tok, arg),
+                        // Compiler options are not tracked, `{$IFOPT}` is always false
+                        _ => false,
+                    };
+                self.stack.push(Branch {
+                    directive: tok.clone(),
+                    parent_active: is_active,
+                    active,
+                    taken: active || !is_active,
+                });
+            }
+            "ELSEIF" => {
+                let Some(branch) = self.stack.last() else {
+                    return self.unmatched(tok);
+                };
+                let active = !branch.taken && self.evaluate(tok, arg);
+                let branch = self.stack.last_mut().unwrap();
+                branch.active = active;
+                branch.taken |= active;
+            }
+            "ELSE" => {
+                let Some(branch) = self.stack.last_mut() else {
+                    return self.unmatched(tok);
+                };
+                branch.active = branch.parent_active && !branch.taken;
+                branch.taken = true;
+            }
+            "ENDIF" | "IFEND" => match self.stack.pop() {
+                Some(_) => {}
+                None => self.unmatched(tok),
+            },
+            "DEFINE" if is_active => self.defines.define(symbol),
+            "UNDEF" if is_active => self.defines.undefine(symbol),
+            _ => {}
+        }
+    }
+
+    fn unmatched(&mut self, tok: &Token) {
+        let message = format!("`{{${}}}` without `{{$IF}}`", tok.content.trim());
+        self.diagnostics
+            .push(Diagnostic::syntax_error(tok, message));
+    }
+
+    /// Evaluates the expression of `{$IF}` and `{$ELSEIF}`, errors count as false. Constants
+    /// without a value count as `0` and unsupported conditions as false, both with a warning
+    fn evaluate(&mut self, tok: &Token, expression: &str) -> bool {
+        let tokens: Vec<Token> = Lexer::new(expression)
+            .filter(|tok| {
+                !matches!(
+                    tok.typ,
+                    TokenTyp::Whitespace | TokenTyp::Comment | TokenTyp::DocComment
+                )
+            })
+            .collect();
+
+        let mut evaluator = Evaluator {
+            expression,
+            tokens: &tokens,
+            pos: 0,
+            defines: &self.defines,
+            unknown: Vec::new(),
+            unsupported: None,
+        };
+        let res = evaluator.parse_or().and_then(|value| {
+            if evaluator.pos < tokens.len() {
+                Err(format!("Unexpected `{}`", tokens[evaluator.pos].content))
+            } else {
+                Ok(value)
+            }
+        });
+
+        for name in evaluator.unknown {
+            let message = format!(
+                "Unknown constant `{name}` counts as 0, set its value with `-D {name}=VALUE` \
+                 or in the `values` of the config"
+            );
+            self.diagnostics.push(Diagnostic::with_span(
+                Severity::Warning,
+                UNKNOWN_CONSTANT,
+                message,
+                tok.span,
+            ));
+        }
+
+        match (res, evaluator.unsupported) {
+            (Ok(_), Some(call)) => {
+                let message = format!(
+                    "Can not evaluate `{{${}}}`, `{call}` is not supported, the condition counts as false",
+                    tok.content.trim()
+                );
+                self.diagnostics.push(Diagnostic::with_span(
+                    Severity::Warning,
+                    UNSUPPORTED_CONDITION,
+                    message,
+                    tok.span,
+                ));
+                false
+            }
+            (Ok(value), None) => value != 0.0,
+            (Err(message), _) => {
+                let message = format!("Can not evaluate `{{${}}}`: {message}", tok.content.trim());
+                self.diagnostics
+                    .push(Diagnostic::syntax_error(tok, message));
+                false
+            }
+        }
+    }
+}
+
+/// Recursive descent evaluation of `{$IF}` expressions, booleans are `1.0` and `0.0`
+struct Evaluator<'a> {
+    expression: &'a str,
+    tokens: &'a [Token<'a>],
+    pos: usize,
+    defines: &'a Defines,
+    /// Constants without a value that were used
+    unknown: Vec<String>,
+    /// The first call that can not be evaluated
+    unsupported: Option<String>,
+}
+
+impl<'a> Evaluator<'a> {
+    fn peek(&self) -> Option<&'a Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is(&self, content: &str) -> bool {
+        self.peek()
+            .is_some_and(|tok| tok.content.eq_ignore_ascii_case(content))
+    }
+
+    fn expect(&mut self, content: &str) -> Result<(), String> {
+        if self.is(content) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("Expected `{content}`"))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<f64, String> {
+        let mut left = self.parse_and()?;
+        loop {
+            let is_xor = self.is("xor");
+            if !is_xor && !self.is("or") {
+                return Ok(left);
+            }
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = if is_xor {
+                bool_value((left != 0.0) != (right != 0.0))
+            } else {
+                bool_value(left != 0.0 || right != 0.0)
+            };
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<f64, String> {
+        let mut left = self.parse_not()?;
+        while self.is("and") {
+            self.pos += 1;
+            let right = self.parse_not()?;
+            left = bool_value(left != 0.0 && right != 0.0);
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<f64, String> {
+        if self.is("not") {
+            self.pos += 1;
+            return Ok(bool_value(self.parse_not()? == 0.0));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<f64, String> {
+        let left = self.parse_additive()?;
+
+        let Some(TokenTyp::Symbol(symbol)) = self.peek().map(|tok| tok.typ.clone()) else {
+            return Ok(left);
+        };
+        let compare: fn(f64, f64) -> bool = match symbol {
+            Symbol::Equal => |a, b| a == b,
+            Symbol::NotEqual => |a, b| a != b,
+            Symbol::Less => |a, b| a < b,
+            Symbol::LessEqual => |a, b| a <= b,
+            Symbol::Greater => |a, b| a > b,
+            Symbol::GreaterEqual => |a, b| a >= b,
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.parse_additive()?;
+
+        Ok(bool_value(compare(left, right)))
+    }
+
+    fn parse_additive(&mut self) -> Result<f64, String> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op: fn(f64, f64) -> f64 = match self.peek().map(|tok| &tok.typ) {
+                Some(TokenTyp::Symbol(Symbol::Plus)) => |a, b| a + b,
+                Some(TokenTyp::Symbol(Symbol::Minus)) => |a, b| a - b,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = op(left, self.parse_multiplicative()?);
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<f64, String> {
+        let mut left = self.parse_primary()?;
+        loop {
+            let op: fn(f64, f64) -> f64 = match self.peek().map(|tok| &tok.typ) {
+                Some(TokenTyp::Symbol(Symbol::Star)) => |a, b| a * b,
+                Some(TokenTyp::Symbol(Symbol::Slash)) => |a, b| a / b,
+                _ if self.is("div") => |a, b| (a / b).trunc(),
+                _ if self.is("mod") => |a, b| a % b,
+                _ if self.is("shl") => |a, b| (a as i64).wrapping_shl(b as u32) as f64,
+                _ if self.is("shr") => |a, b| (a as i64).wrapping_shr(b as u32) as f64,
+                _ => return Ok(left),
+            };
+            self.pos += 1;
+            left = op(left, self.parse_primary()?);
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<f64, String> {
+        let Some(tok) = self.peek().cloned() else {
+            return Err(String::from("Unexpected end of expression"));
+        };
+        self.pos += 1;
+
+        match tok.typ {
+            TokenTyp::Number(Number::Integer(value)) => Ok(value as f64),
+            TokenTyp::Number(Number::Real(value)) => Ok(value),
+            TokenTyp::Number(Number::Invalid) => Err(format!("Invalid number `{}`", tok.content)),
+            TokenTyp::Symbol(Symbol::LeftParen) => {
+                let value = self.parse_or()?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            TokenTyp::Symbol(Symbol::Minus) => Ok(-self.parse_primary()?),
+            TokenTyp::Identifier | TokenTyp::Directive | TokenTyp::Keyword => {
+                let name = tok.content.to_lowercase();
+                if name == "defined" || name == "declared" {
+                    self.expect("(")?;
+                    let symbol = self
+                        .peek()
+                        .map(|tok| tok.content.clone())
+                        .ok_or_else(|| String::from("Expected a symbol"))?;
+                    self.pos += 1;
+                    self.expect(")")?;
+                    // Declarations are not tracked, only constants with a value count as declared
+                    return Ok(bool_value(if name == "defined" {
+                        self.defines.is_defined(&symbol)
+                    } else {
+                        self.defines.value(&symbol).is_some()
+                    }));
+                }
+
+                if self.is("(") {
+                    return self.parse_call(&tok.content);
+                }
+
+                match name.as_str() {
+                    "true" => Ok(1.0),
+                    "false" => Ok(0.0),
+                    _ => Ok(self.defines.value(&tok.content).unwrap_or_else(|| {
+                        if !self.unknown.contains(&tok.content.to_string()) {
+                            self.unknown.push(tok.content.to_string());
+                        }
+                        0.0
+                    })),
+                }
+            }
+            _ => Err(format!("Unexpected `{}`", tok.content)),
+        }
+    }
+
+    /// `SizeOf`, `High` and `Low` of the basic types and `Ord`. Other calls count as `0` and
+    /// make the whole condition unsupported
+    fn parse_call(&mut self, name: &str) -> Result<f64, String> {
+        self.expect("(")?;
+        let start = self.pos;
+
+        let value = match name.to_lowercase().as_str() {
+            "ord" => Some(self.parse_or()?),
+            function @ ("sizeof" | "high" | "low") => {
+                let typ = self
+                    .peek()
+                    .map(|tok| tok.content.to_lowercase())
+                    .ok_or_else(|| String::from("Expected a type"))?;
+                self.pos += 1;
+                match function {
+                    "sizeof" => type_size(&typ, self.pointer_size()),
+                    "high" => int_range(&typ).map(|(_, high)| high),
+                    _ => int_range(&typ).map(|(low, _)| low),
+                }
+            }
+            _ => None,
+        };
+
+        let Some(value) = value else {
+            // Skips the arguments up to the matching `)`
+            self.pos = start;
+            let mut depth = 0;
+            while let Some(tok) = self.peek() {
+                match tok.typ {
+                    TokenTyp::Symbol(Symbol::LeftParen) => depth += 1,
+                    TokenTyp::Symbol(Symbol::RightParen) if depth == 0 => break,
+                    TokenTyp::Symbol(Symbol::RightParen) => depth -= 1,
+                    _ => {}
+                }
+                self.pos += 1;
+            }
+            self.expect(")")?;
+            // From the name to the `)`
+            let call = &self.expression[self.tokens[start - 2].span.start.offset
+                ..self.tokens[self.pos - 1].span.end.offset];
+            self.unsupported.get_or_insert_with(|| String::from(call));
+            return Ok(0.0);
+        };
+
+        self.expect(")")?;
+        Ok(value)
+    }
+
+    /// 8 if one of the symbols of 64-bit targets is defined, otherwise 4
+    fn pointer_size(&self) -> f64 {
+        let is_64_bit = ["CPU64BITS", "CPUX64", "CPUARM64", "WIN64"]
+            .iter()
+            .any(|symbol| self.defines.is_defined(symbol));
+        if is_64_bit { 8.0 } else { 4.0 }
+    }
+}
+
+/// Size in bytes of a basic type, references like `Pointer` or `string` have `pointer_size`
+fn type_size(typ: &str, pointer_size: f64) -> Option<f64> {
+    match typ {
+        "byte" | "shortint" | "boolean" | "bytebool" | "ansichar" => Some(1.0),
+        "word" | "smallint" | "char" | "widechar" | "wordbool" => Some(2.0),
+        "integer" | "cardinal" | "longint" | "longword" | "longbool" | "single" => Some(4.0),
+        "int64" | "uint64" | "double" | "currency" | "comp" => Some(8.0),
+        "pointer" | "nativeint" | "nativeuint" | "string" | "ansistring" | "unicodestring"
+        | "widestring" | "tobject" | "iinterface" => Some(pointer_size),
+        _ => None,
+    }
+}
+
+/// `Low` and `High` of an ordinal type with a fixed size
+fn int_range(typ: &str) -> Option<(f64, f64)> {
+    match typ {
+        "boolean" => Some((0.0, 1.0)),
+        "shortint" => Some((i8::MIN.into(), i8::MAX.into())),
+        "byte" | "ansichar" => Some((0.0, u8::MAX.into())),
+        "smallint" => Some((i16::MIN.into(), i16::MAX.into())),
+        "word" | "char" | "widechar" => Some((0.0, u16::MAX.into())),
+        "integer" | "longint" => Some((i32::MIN.into(), i32::MAX.into())),
+        "cardinal" | "longword" => Some((0.0, u32::MAX.into())),
+        "int64" => Some((i64::MIN as f64, i64::MAX as f64)),
+        "uint64" => Some((0.0, u64::MAX as f64)),
+        _ => None,
+    }
+}
+
+fn bool_value(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preprocessor::*;
+
+    fn active_tokens(content: &str, symbols: &[&str]) -> (Vec<String>, Vec<Diagnostic>) {
+        let mut defines = Defines::new(symbols);
+        defines.set_value("CompilerVersion", 35.0);
+        let mut pre = Preprocessor::new(content, defines);
+
+        let mut res = Vec::new();
+        loop {
+            let tok = pre.next();
+            match tok.typ {
+                TokenTyp::EOF => break,
+                TokenTyp::Identifier => res.push(tok.content.into_owned()),
+                _ => {}
+            }
+        }
+        (res, pre.diagnostics())
+    }
+
+    #[test]
+    fn ifdef() {
+        let content = "{$IFDEF DEBUG} a {$ELSE} b {$ENDIF} {$ifndef debug} c {$endif} d";
+
+        assert_eq!(active_tokens(content, &["Debug"]).0, vec!["a", "d"]);
+        assert_eq!(active_tokens(content, &[]).0, vec!["b", "c", "d"]);
+    }
+
+    #[test]
+    fn nested() {
+        let content = "{$IFDEF A} a {$IFDEF B} ab {$ELSE} aa {$ENDIF} {$ELSE} x {$IFDEF B} xb {$ENDIF} {$ENDIF}";
+
+        assert_eq!(active_tokens(content, &["A", "B"]).0, vec!["a", "ab"]);
+        assert_eq!(active_tokens(content, &["A"]).0, vec!["a", "aa"]);
+        assert_eq!(active_tokens(content, &["B"]).0, vec!["x", "xb"]);
+        assert_eq!(active_tokens(content, &[]).0, vec!["x"]);
+    }
+
+    #[test]
+    fn if_expressions() {
+        let content = "{$IF Defined(MSWINDOWS) and not Defined(CPUX64)} win32 \
+            {$ELSEIF Defined(MSWINDOWS) or (CompilerVersion >= 36)} win64 \
+            {$ELSEIF True} other {$ELSE} never {$IFEND}";
+
+        assert_eq!(active_tokens(content, &["MSWINDOWS"]).0, vec!["win32"]);
+        assert_eq!(
+            active_tokens(content, &["MSWINDOWS", "CPUX64"]).0,
+            vec!["win64"]
+        );
+        assert_eq!(active_tokens(content, &[]).0, vec!["other"]);
+        assert_eq!(
+            active_tokens("{$IF CompilerVersion = 35.0} a {$ENDIF}", &[]).0,
+            vec!["a"]
+        );
+        assert_eq!(active_tokens("{$IF $23 = 35} a {$ENDIF}", &[]).0, vec!["a"]);
+    }
+
+    #[test]
+    fn define_and_undef() {
+        let content = "{$DEFINE FOO} {$IFDEF FOO} a {$ENDIF} {$IFDEF BAR} {$UNDEF FOO} {$ENDIF} \
+            {$UNDEF foo} {$IFDEF FOO} b {$ENDIF}";
+
+        assert_eq!(active_tokens(content, &[]).0, vec!["a"]);
+    }
+
+    #[test]
+    fn directive_errors() {
+        let (tokens, diagnostics) =
+            active_tokens("{$ENDIF} {$IF 1 >} a {$ENDIF} {$IFDEF X} b", &["X"]);
+
+        assert_eq!(tokens, vec!["b"]);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`{$ENDIF}` without `{$IF}`",
+                "Can not evaluate `{$IF 1 >}`: Unexpected end of expression",
+                "Missing `{$ENDIF}` for `{$IFDEF X}`",
+            ]
+        );
+    }
+
+    #[test]
+    fn intrinsics_and_arithmetic() {
+        let content = "{$IF SizeOf(Pointer) = 8} x64 {$ELSE} x86 {$ENDIF} \
+            {$IF High(Integer) + 1 = 1 shl 31} high {$ENDIF} \
+            {$IF (Ord(True) * 10 div 3 = 3) xor (7 mod 4 <> 3)} ord {$ENDIF} \
+            {$IF CompilerVersion - 0.5 >= Low(Byte) + 34} version {$ENDIF}";
+
+        let (tokens, diagnostics) = active_tokens(content, &["CPUX64"]);
+        assert_eq!(tokens, vec!["x64", "high", "ord", "version"]);
+        assert!(diagnostics.is_empty());
+        assert_eq!(active_tokens(content, &[]).0[0], "x86");
+    }
+
+    #[test]
+    fn unsupported_conditions() {
+        let (tokens, diagnostics) = active_tokens(
+            "{$IF SizeOf(TFoo) = 8} a {$ELSEIF Length(Foo(1, 2)) > 0} b {$ELSE} c {$ENDIF}",
+            &[],
+        );
+
+        assert_eq!(tokens, vec!["c"]);
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Can not evaluate `{$IF SizeOf(TFoo) = 8}`, `SizeOf(TFoo)` is not supported, \
+                 the condition counts as false",
+                "Can not evaluate `{$ELSEIF Length(Foo(1, 2)) > 0}`, `Length(Foo(1, 2))` is not \
+                 supported, the condition counts as false",
+            ]
+        );
+        assert!(
+            diagnostics
+                .iter()
+                .all(|d| d.severity == Severity::Warning && d.code == UNSUPPORTED_CONDITION)
+        );
+    }
+
+    #[test]
+    fn unknown_constants() {
+        let (tokens, diagnostics) = active_tokens(
+            "{$IF RTLVersion >= 35} a {$ELSEIF RTLVersion < 1} b {$ENDIF}",
+            &[],
+        );
+
+        assert_eq!(tokens, vec!["b"]);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].code, UNKNOWN_CONSTANT);
+        assert!(
+            diagnostics[0]
+                .message
+                .starts_with("Unknown constant `RTLVersion` counts as 0")
+        );
+        assert_eq!((diagnostics[1].row, diagnostics[1].col), (1, 26));
+    }
+
+    #[test]
+    fn other_directives_are_kept() {
+        let mut pre = Preprocessor::new("{$R *.res}", Defines::default());
+
+        let tok = pre.next();
+
+        assert_eq!(tok.typ, TokenTyp::ConditionalCompilation);
+        assert_eq!(tok.content, "R *.res");
+    }
+}
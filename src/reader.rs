@@ -1,45 +1,70 @@
-pub struct Reader {
-    chars: Vec<char>,
+use serde::Serialize;
+
+/// A location in the source, `offset` is in bytes, `row` and `col` are 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct Position {
+    pub offset: usize,
+    pub row: usize,
+    pub col: usize,
+}
+
+/// Reads the characters of a borrowed source, the texts it returns are slices of it
+pub struct Reader<'a> {
+    text: &'a str,
+    /// Index of the current character
     i: usize,
+    /// Byte offset of the current character
+    pub offset: usize,
     pub row: usize,
     pub col: usize,
 }
 
-impl Reader {
-    pub fn new(content: String) -> Self {
-        let charas: Vec<char> = content.chars().collect();
+impl<'a> Reader<'a> {
+    pub fn new(content: &'a str) -> Self {
         Reader {
-            chars: charas,
+            text: content,
             i: 0,
+            offset: 0,
             row: 1, // Delphi uses 1-based line numbers
             col: 1, // Delphi uses 1-based column numbers
         }
     }
 
-    /// Returns the current character and advances to the next
+    /// Returns the current character and advances to the next. `\r\n`, `\r` and `\n` are
+    /// line breaks
+    #[allow(clippy::should_implement_trait)]
+    #[inline]
     pub fn next(&mut self) -> Option<char> {
-        if let Some(c) = self.chars.get(self.i) {
-            if *c == '\n' {
+        if let Some(c) = self.peek() {
+            if c == '\n' || (c == '\r' && self.peek_nth(1) != Some('\n')) {
                 self.row += 1;
                 self.col = 1;
             } else {
                 self.col += 1;
             }
             self.i += 1;
-            return Some(*c);
+            self.offset += c.len_utf8();
+            return Some(c);
         }
 
-        return None;
+        None
     }
 
     /// Peeks at the next character without advancing
+    #[inline]
     pub fn peek(&mut self) -> Option<char> {
         self.peek_nth(0)
     }
 
     /// Peeks at a character at a specific offset from current position
+    #[inline]
     pub fn peek_nth(&self, offset: usize) -> Option<char> {
-        self.chars.get(self.i + offset).copied()
+        // `nth` is optimized for skipping many characters, the offsets here are small
+        let mut chars = self.rest().chars();
+        for _ in 0..offset {
+            chars.next()?;
+        }
+        chars.next()
     }
 
     /// Advances by multiple characters
@@ -50,45 +75,39 @@ impl Reader {
     }
 
     // Advance the reader until the target character is reached and return all of the characters found
-    pub fn read_until(&mut self, target: char) -> String {
-        let mut res = String::new();
+    pub fn read_until(&mut self, target: char) -> &'a str {
+        let start = self.position();
         while let Some(c) = self.peek() {
             if c == target {
                 break;
             }
-            if let Some(c) = self.next() {
-                res.push(c);
-            }
+            self.next();
         }
-        return res;
+        self.text_from(start)
     }
 
     // Advance the reader until the target character is reached and return all of the characters found
-    pub fn read_until_any(&mut self, targets: &[char]) -> String {
-        let mut res = String::new();
+    pub fn read_until_any(&mut self, targets: &[char]) -> &'a str {
+        let start = self.position();
         while let Some(c) = self.peek() {
             if targets.contains(&c) {
                 break;
             }
-            if let Some(c) = self.next() {
-                res.push(c);
-            }
+            self.next();
         }
-        return res;
+        self.text_from(start)
     }
 
     // Advances the reader until a character that is not equal to `target` is encountered. Return all characters found
-    pub fn read_until_not(&mut self, target: char) -> String {
-        let mut res = String::new();
+    pub fn read_until_not(&mut self, target: char) -> &'a str {
+        let start = self.position();
         while let Some(c) = self.peek() {
             if c != target {
                 break;
             }
-            if let Some(c) = self.next() {
-                res.push(c);
-            }
+            self.next();
         }
-        return res;
+        self.text_from(start)
     }
 
     // Count until the character is passed
@@ -104,17 +123,51 @@ impl Reader {
             }
             i += 1;
         }
-        return quote_count;
+        quote_count
+    }
+
+    /// Advances until the rest of the text starts with `target` or is empty and returns
+    /// the text before it
+    pub fn read_until_str(&mut self, target: &str) -> &'a str {
+        let rest = self.rest();
+        let res = &rest[..rest.find(target).unwrap_or(rest.len())];
+        self.advance_by(res.chars().count());
+        res
+    }
+
+    /// The text from the current position to the end
+    #[inline]
+    pub fn rest(&self) -> &'a str {
+        &self.text[self.offset..]
+    }
+
+    /// The text from `start` up to the current position
+    pub fn text_from(&self, start: Position) -> &'a str {
+        &self.text[start.offset.min(self.offset)..self.offset]
+    }
+
+    /// Like `count_until_not` but starting `offset` characters ahead
+    pub fn count_until_not_at(&self, offset: usize, target: char) -> usize {
+        let mut count = 0;
+        while self.peek_nth(offset + count) == Some(target) {
+            count += 1;
+        }
+        count
     }
 
-    /// Gets the current position (1-based)
-    pub fn position(&self) -> (usize, usize) {
-        (self.row, self.col)
+    /// Gets the current position
+    #[inline]
+    pub fn position(&self) -> Position {
+        Position {
+            offset: self.offset,
+            row: self.row,
+            col: self.col,
+        }
     }
 
     /// Checks if we've reached the end of input
     pub fn is_eof(&self) -> bool {
-        self.i >= self.chars.len()
+        self.offset >= self.text.len()
     }
 }
 
@@ -124,7 +177,7 @@ mod tests {
 
     #[test]
     fn empty() {
-        let mut reader = Reader::new(String::from(""));
+        let mut reader = Reader::new("");
 
         assert_eq!(reader.i, 0);
         let c = reader.next();
@@ -134,7 +187,7 @@ mod tests {
 
     #[test]
     fn i() {
-        let mut reader = Reader::new(String::from("12"));
+        let mut reader = Reader::new("12");
 
         let _ = reader.next();
 
@@ -149,7 +202,7 @@ mod tests {
 
     #[test]
     fn col() {
-        let mut reader = Reader::new(String::from("1"));
+        let mut reader = Reader::new("1");
 
         let _ = reader.next();
 
@@ -159,7 +212,7 @@ mod tests {
 
     #[test]
     fn row() {
-        let mut reader = Reader::new(String::from("\n"));
+        let mut reader = Reader::new("\n");
 
         let _ = reader.next();
 
@@ -169,33 +222,33 @@ mod tests {
 
     #[test]
     fn advance_by() {
-        let mut reader = Reader::new(String::from("1234"));
+        let mut reader = Reader::new("1234");
 
-        let _ = reader.advance_by(1);
+        reader.advance_by(1);
 
         assert_eq!(reader.i, 1);
 
-        let _ = reader.advance_by(1);
+        reader.advance_by(1);
 
         assert_eq!(reader.i, 2);
 
-        let _ = reader.advance_by(2);
+        reader.advance_by(2);
 
         assert_eq!(reader.i, 4);
     }
 
     #[test]
     fn read_until() {
-        let mut reader = Reader::new(String::from("1234"));
+        let mut reader = Reader::new("1234");
 
         let res = reader.read_until('4');
 
-        assert_eq!(res.as_str(), "123");
+        assert_eq!(res, "123");
     }
 
     #[test]
     fn count_until_not() {
-        let mut reader = Reader::new(String::from("   4"));
+        let mut reader = Reader::new("   4");
 
         let res = reader.count_until_not(' ');
 
@@ -204,19 +257,67 @@ mod tests {
 
     #[test]
     fn read_until_not() {
-        let mut reader = Reader::new(String::from("   4"));
+        let mut reader = Reader::new("   4");
 
         let res = reader.read_until_not(' ');
 
-        assert_eq!(res.as_str(), "   ");
+        assert_eq!(res, "   ");
     }
 
     #[test]
     fn read_until_any() {
-        let mut reader = Reader::new(String::from("   4"));
+        let mut reader = Reader::new("   4");
 
         let res = reader.read_until_any(&['4']);
 
-        assert_eq!(res.as_str(), "   ");
+        assert_eq!(res, "   ");
+    }
+
+    #[test]
+    fn position() {
+        let mut reader = Reader::new("aä\n€b");
+
+        reader.advance_by(2);
+        assert_eq!(
+            reader.position(),
+            Position {
+                offset: 3,
+                row: 1,
+                col: 3
+            }
+        );
+
+        reader.advance_by(2);
+        assert_eq!(
+            reader.position(),
+            Position {
+                offset: 7,
+                row: 2,
+                col: 2
+            }
+        );
+    }
+
+    #[test]
+    fn line_breaks() {
+        let mut reader = Reader::new("a\r\nb\rc\nd");
+        let mut rows = Vec::new();
+        while let Some(c) = reader.next() {
+            rows.push((c, reader.row, reader.col));
+        }
+
+        assert_eq!(
+            rows,
+            vec![
+                ('a', 1, 2),
+                ('\r', 1, 3),
+                ('\n', 2, 1),
+                ('b', 2, 2),
+                ('\r', 3, 1),
+                ('c', 3, 2),
+                ('\n', 4, 1),
+                ('d', 4, 2)
+            ]
+        );
     }
 }
@@ -0,0 +1,763 @@
+use toml::Table;
+
+use crate::ast::*;
+use crate::config;
+use crate::diagnostic::{Diagnostic, SYNTAX_ERROR, Severity, merge_configuration};
+use crate::lexer::{Lexer, Span, Token, TokenTyp};
+use crate::parser::Parser;
+use crate::preprocessor::{Defines, UNKNOWN_CONSTANT, UNSUPPORTED_CONDITION};
+use crate::rules;
+use crate::suppression::{UNUSED_SUPPRESSION, apply_suppressions, parse_suppressions};
+
+/// A lint check. Rules only implement the callbacks they need, the `Registry` walks the
+/// tokens and the AST and calls them for every node
+pub trait Rule {
+    /// Unique kebab-case id used on the command line, in configs and in suppressions
+    fn id(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Applies the rule specific options of the config, rules without options reject every key
+    fn configure(&mut self, options: &Table) -> Result<(), String> {
+        match options.keys().next() {
+            Some(key) => Err(config::unknown_option(key)),
+            None => Ok(()),
+        }
+    }
+
+    /// Called for every token except whitespace
+    fn check_token(&self, _tok: &Token, _ctx: &mut Context) {}
+
+    /// Called once per file before any other AST callback
+    fn check_file(&self, _file: &File, _ctx: &mut Context) {}
+
+    fn check_type_declaration(&self, _decl: &TypeDeclaration, _ctx: &mut Context) {}
+
+    /// Called for the declarations in the interface section and in classes
+    fn check_method_head(&self, _head: &MethodHead, _ctx: &mut Context) {}
+
+    /// Called for methods with an implementation
+    fn check_method(&self, _method: &Method, _ctx: &mut Context) {}
+
+    /// Called for every statement, report at its position with `Context::report_span`
+    fn check_statement(&self, _statement: &Statement, _ctx: &mut Context) {}
+
+    /// Called for every expression including the nested ones
+    fn check_expression(&self, _expression: &Expression, _ctx: &mut Context) {}
+}
+
+/// Collects the diagnostics of the rule that is currently running
+pub struct Context {
+    rule: &'static str,
+    severity: Severity,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Context {
+    pub fn report(&mut self, message: String, row: usize, col: usize) {
+        let diagnostic = Diagnostic::new(self.severity, self.rule, message, row, col);
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Reports a diagnostic covering `tok`
+    pub fn report_token(&mut self, tok: &Token, message: String) {
+        self.report_span(tok.span, message);
+    }
+
+    /// Reports a diagnostic covering `span`, e.g. the span of a statement or expression
+    pub fn report_span(&mut self, span: Span, message: String) {
+        let diagnostic = Diagnostic::with_span(self.severity, self.rule, message, span);
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+struct Entry {
+    rule: Box<dyn Rule>,
+    severity: Severity,
+    enabled: bool,
+}
+
+/// The set of rules that is run over a file
+#[derive(Default)]
+pub struct Registry {
+    entries: Vec<Entry>,
+    /// Conditional symbols for the parser, rules see only the active branches
+    defines: Defines,
+    /// Named define sets that are checked one after another in addition to `defines`
+    configurations: Vec<(String, Defines)>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            entries: Vec::new(),
+            defines: Defines::default(),
+            configurations: Vec::new(),
+        }
+    }
+
+    /// A registry with every rule of the `rules` module enabled
+    pub fn with_builtin_rules() -> Self {
+        let mut registry = Registry::new();
+        for rule in rules::builtin_rules() {
+            registry.register(rule);
+        }
+        registry
+    }
+
+    /// Adds an enabled rule with its default severity, a rule with the same id is replaced
+    pub fn register(&mut self, rule: Box<dyn Rule>) {
+        self.entries.retain(|entry| entry.rule.id() != rule.id());
+        self.entries.push(Entry {
+            severity: rule.default_severity(),
+            rule,
+            enabled: true,
+        });
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &dyn Rule> {
+        self.entries.iter().map(|entry| entry.rule.as_ref())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&dyn Rule> {
+        self.entry(id).map(|entry| entry.rule.as_ref())
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        self.entry(id).is_some_and(|entry| entry.enabled)
+    }
+
+    /// Whether the rule with the id is enabled, `None` for ids that are neither a rule nor
+    /// the code of a diagnostic without a rule like `syntax-error`
+    fn rule_state(&self, id: &str) -> Option<bool> {
+        match self.entry(id) {
+            Some(entry) => Some(entry.enabled),
+            None => [SYNTAX_ERROR, UNKNOWN_CONSTANT, UNSUPPORTED_CONDITION]
+                .contains(&id)
+                .then_some(true),
+        }
+    }
+
+    /// Returns `false` if no rule has the id
+    pub fn set_enabled(&mut self, id: &str, enabled: bool) -> bool {
+        self.entry_mut(id)
+            .map(|entry| entry.enabled = enabled)
+            .is_some()
+    }
+
+    /// Returns `false` if no rule has the id
+    pub fn set_severity(&mut self, id: &str, severity: Severity) -> bool {
+        self.entry_mut(id)
+            .map(|entry| entry.severity = severity)
+            .is_some()
+    }
+
+    pub fn defines(&self) -> &Defines {
+        &self.defines
+    }
+
+    pub fn set_defines(&mut self, defines: Defines) {
+        self.defines = defines;
+    }
+
+    pub fn configurations(&self) -> &[(String, Defines)] {
+        &self.configurations
+    }
+
+    /// Files are parsed once per configuration and the diagnostics tagged with the
+    /// configurations they were found in. Without configurations only `defines` is used
+    pub fn set_configurations(&mut self, configurations: Vec<(String, Defines)>) {
+        self.configurations = configurations;
+    }
+
+    /// Passes the options of the config to the rule
+    pub fn configure(&mut self, id: &str, options: &Table) -> Result<(), String> {
+        match self.entry_mut(id) {
+            Some(entry) => entry.rule.configure(options),
+            None => Err(format!("Unknown rule `{id}`")),
+        }
+    }
+
+    fn entry(&self, id: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.rule.id() == id)
+    }
+
+    fn entry_mut(&mut self, id: &str) -> Option<&mut Entry> {
+        self.entries.iter_mut().find(|entry| entry.rule.id() == id)
+    }
+
+    /// Lexes and parses `content` and runs every enabled rule over it.
+    /// The result contains the syntax errors too, without the suppressed diagnostics and sorted by position
+    pub fn check(&self, content: &str) -> Vec<Diagnostic> {
+        let (tokens, _) = lex(content);
+
+        let res = if self.configurations.is_empty() {
+            self.check_defines(content, &tokens, self.defines.clone())
+        } else {
+            let mut res = Vec::new();
+            for (name, defines) in &self.configurations {
+                let mut all = self.defines.clone();
+                all.extend(defines);
+                merge_configuration(&mut res, self.check_defines(content, &tokens, all), name);
+            }
+            res
+        };
+
+        // Suppressions are applied to the merged diagnostics, so a suppression that is
+        // only used in one configuration does not count as unused
+        self.suppress(&tokens, res)
+    }
+
+    fn check_defines(&self, content: &str, tokens: &[Token], defines: Defines) -> Vec<Diagnostic> {
+        let mut parser = Parser::with_defines(content, defines);
+        let file = parser.parse_file();
+
+        let mut res = parser.diagnostics();
+        res.append(&mut self.check_file(&file, tokens));
+        res
+    }
+
+    /// Runs only the token callbacks, for files that are not a complete unit like `.inc` files
+    pub fn check_tokens(&self, content: &str) -> Vec<Diagnostic> {
+        let (tokens, mut res) = lex(content);
+
+        let mut walker = self.walker();
+        walker.walk_tokens(&tokens);
+
+        res.append(&mut walker.ctx.diagnostics);
+        self.suppress(&tokens, res)
+    }
+
+    /// Drops the diagnostics suppressed by comments and reports unused suppressions
+    /// if the `unused-suppression` rule is enabled
+    fn suppress(&self, tokens: &[Token], diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let (suppressions, mut invalid) = parse_suppressions(tokens);
+        let unused = self
+            .entry(UNUSED_SUPPRESSION)
+            .filter(|entry| entry.enabled)
+            .map(|entry| entry.severity);
+
+        let mut res =
+            apply_suppressions(&suppressions, diagnostics, unused, |id| self.rule_state(id));
+        if let Some(severity) = unused {
+            for d in &mut invalid {
+                d.severity = severity;
+            }
+            res.append(&mut invalid);
+        }
+        res.sort_by_key(|d| (d.row, d.col));
+        res
+    }
+
+    /// Runs every enabled rule over an already parsed file
+    pub fn check_file(&self, file: &File, tokens: &[Token]) -> Vec<Diagnostic> {
+        let mut walker = self.walker();
+        walker.walk_tokens(tokens);
+        walker.walk_file(file);
+
+        walker.ctx.diagnostics
+    }
+
+    fn walker(&self) -> Walker<'_> {
+        Walker {
+            rules: self
+                .entries
+                .iter()
+                .filter(|entry| entry.enabled)
+                .map(|entry| (entry.rule.as_ref(), entry.severity))
+                .collect(),
+            ctx: Context {
+                rule: "",
+                severity: Severity::Warning,
+                diagnostics: Vec::new(),
+            },
+        }
+    }
+}
+
+/// All tokens of `content` and the diagnostics of the lexer
+fn lex(content: &str) -> (Vec<Token<'_>>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::new(content);
+    let tokens = lexer.by_ref().collect();
+
+    (tokens, lexer.diagnostics().to_vec())
+}
+
+/// Walks the AST and calls the callbacks of every rule for each node
+struct Walker<'a> {
+    rules: Vec<(&'a dyn Rule, Severity)>,
+    ctx: Context,
+}
+
+impl Walker<'_> {
+    fn each(&mut self, f: impl Fn(&dyn Rule, &mut Context)) {
+        for (rule, severity) in &self.rules {
+            self.ctx.rule = rule.id();
+            self.ctx.severity = *severity;
+            f(*rule, &mut self.ctx);
+        }
+    }
+
+    fn walk_tokens(&mut self, tokens: &[Token]) {
+        for tok in tokens {
+            if tok.typ != TokenTyp::Whitespace {
+                self.each(|rule, ctx| rule.check_token(tok, ctx));
+            }
+        }
+    }
+
+    fn walk_file(&mut self, file: &File) {
+        self.each(|rule, ctx| rule.check_file(file, ctx));
+
+        match file {
+            File::Unit(unit) => self.walk_unit(unit),
+            File::Program(program) | File::Library(program) => {
+                self.walk_implementation(&program.declarations);
+                self.walk_statements(&program.body);
+            }
+            File::Package(_) => {}
+        }
+    }
+
+    fn walk_unit(&mut self, unit: &Unit) {
+        for part in &unit.interface {
+            match part {
+                Interface::Imports(_) => {}
+                Interface::Constant(consts) => self.walk_constants(consts),
+                Interface::Definition(types) => self.walk_type_declarations(types),
+                Interface::Variable(vars) => self.walk_variables(vars),
+                Interface::Method(head) => {
+                    self.each(|rule, ctx| rule.check_method_head(head, ctx));
+                }
+            }
+        }
+        self.walk_implementation(&unit.implementation);
+        self.walk_statements(&unit.initialization);
+        self.walk_statements(&unit.finalization);
+    }
+
+    fn walk_implementation(&mut self, parts: &[Implementation]) {
+        for part in parts {
+            match part {
+                Implementation::Imports(_)
+                | Implementation::Label(_)
+                | Implementation::Exports(_) => {}
+                Implementation::Constant(consts) => self.walk_constants(consts),
+                Implementation::Definition(types) => self.walk_type_declarations(types),
+                Implementation::Variable(vars) => self.walk_variables(vars),
+                Implementation::Method(method) => {
+                    self.each(|rule, ctx| rule.check_method(method, ctx));
+                    self.walk_implementation(&method.declarations);
+                    if let Some(body) = &method.body {
+                        self.walk_statements(body);
+                    }
+                }
+            }
+        }
+    }
+
+    fn walk_constants(&mut self, consts: &[Constant]) {
+        for constant in consts {
+            self.walk_expression(&constant.value);
+        }
+    }
+
+    fn walk_variables(&mut self, vars: &[Variable]) {
+        for var in vars {
+            self.walk_type(&var.typ);
+            if let Some(value) = &var.value {
+                self.walk_expression(value);
+            }
+        }
+    }
+
+    fn walk_type_declarations(&mut self, types: &[TypeDeclaration]) {
+        for decl in types {
+            self.each(|rule, ctx| rule.check_type_declaration(decl, ctx));
+            match &decl.definition {
+                TypeDefinition::Class(class) => self.walk_class(class),
+                TypeDefinition::Alias(typ) => self.walk_type(typ),
+                TypeDefinition::Forward(_) => {}
+            }
+        }
+    }
+
+    fn walk_class(&mut self, class: &Class) {
+        for section in &class.sections {
+            for member in &section.members {
+                match member {
+                    ClassMember::Field(var) | ClassMember::ClassVar(var) => {
+                        self.walk_variables(std::slice::from_ref(var));
+                    }
+                    ClassMember::Method(head) => {
+                        self.each(|rule, ctx| rule.check_method_head(head, ctx));
+                    }
+                    ClassMember::Property(_) => {}
+                    ClassMember::Constant(consts) => self.walk_constants(consts),
+                    ClassMember::Definition(types) => self.walk_type_declarations(types),
+                }
+            }
+        }
+    }
+
+    /// Only anonymous records contain nodes the rules are interested in
+    fn walk_type(&mut self, typ: &Type) {
+        if let Type::Record(class) = typ {
+            self.walk_class(class);
+        }
+    }
+
+    fn walk_statements(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.walk_statement(statement);
+        }
+    }
+
+    fn walk_optional_statement(&mut self, statement: &Option<Box<Statement>>) {
+        if let Some(statement) = statement {
+            self.walk_statement(statement);
+        }
+    }
+
+    fn walk_statement(&mut self, statement: &Statement) {
+        self.each(|rule, ctx| rule.check_statement(statement, ctx));
+
+        match &statement.kind {
+            StatementKind::Compound(body) => self.walk_statements(body),
+            StatementKind::Assignment { target, value } => {
+                self.walk_expression(target);
+                self.walk_expression(value);
+            }
+            StatementKind::Call(expression) => self.walk_expression(expression),
+            StatementKind::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.walk_expression(condition);
+                self.walk_optional_statement(then_branch);
+                self.walk_optional_statement(else_branch);
+            }
+            StatementKind::Case {
+                selector,
+                branches,
+                else_branch,
+            } => {
+                self.walk_expression(selector);
+                for branch in branches {
+                    self.walk_expressions(&branch.labels);
+                    if let Some(body) = &branch.body {
+                        self.walk_statement(body);
+                    }
+                }
+                if let Some(else_branch) = else_branch {
+                    self.walk_statements(else_branch);
+                }
+            }
+            StatementKind::For {
+                start, end, body, ..
+            } => {
+                self.walk_expression(start);
+                self.walk_expression(end);
+                self.walk_optional_statement(body);
+            }
+            StatementKind::ForIn {
+                collection, body, ..
+            } => {
+                self.walk_expression(collection);
+                self.walk_optional_statement(body);
+            }
+            StatementKind::While { condition, body } => {
+                self.walk_expression(condition);
+                self.walk_optional_statement(body);
+            }
+            StatementKind::Repeat { body, condition } => {
+                self.walk_statements(body);
+                self.walk_expression(condition);
+            }
+            StatementKind::Try { body, handler } => {
+                self.walk_statements(body);
+                match handler {
+                    TryHandler::Except(block) => {
+                        for handler in &block.handlers {
+                            if let Some(body) = &handler.body {
+                                self.walk_statement(body);
+                            }
+                        }
+                        self.walk_statements(&block.statements);
+                    }
+                    TryHandler::Finally(statements) => self.walk_statements(statements),
+                }
+            }
+            StatementKind::With { objects, body } => {
+                self.walk_expressions(objects);
+                self.walk_optional_statement(body);
+            }
+            StatementKind::Raise { exception, at } => {
+                if let Some(exception) = exception {
+                    self.walk_expression(exception);
+                }
+                if let Some(at) = at {
+                    self.walk_expression(at);
+                }
+            }
+            StatementKind::Exit(value) => {
+                if let Some(value) = value {
+                    self.walk_expression(value);
+                }
+            }
+            StatementKind::Labeled { statement, .. } => self.walk_optional_statement(statement),
+            StatementKind::Variable { value, .. } => {
+                if let Some(value) = value {
+                    self.walk_expression(value);
+                }
+            }
+            StatementKind::Constant { value, .. } => self.walk_expression(value),
+            StatementKind::Break
+            | StatementKind::Continue
+            | StatementKind::Goto(_)
+            | StatementKind::Asm(_) => {}
+        }
+    }
+
+    fn walk_expressions(&mut self, expressions: &[Expression]) {
+        for expression in expressions {
+            self.walk_expression(expression);
+        }
+    }
+
+    fn walk_expression(&mut self, expression: &Expression) {
+        self.each(|rule, ctx| rule.check_expression(expression, ctx));
+
+        match &expression.kind {
+            ExpressionKind::Set(elements) | ExpressionKind::ArrayConstant(elements) => {
+                self.walk_expressions(elements);
+            }
+            ExpressionKind::Range(start, end) => {
+                self.walk_expression(start);
+                self.walk_expression(end);
+            }
+            ExpressionKind::RecordConstant(fields) => {
+                for (_, value) in fields {
+                    self.walk_expression(value);
+                }
+            }
+            ExpressionKind::Unary { operand, .. } => self.walk_expression(operand),
+            ExpressionKind::Binary { left, right, .. } => {
+                self.walk_expression(left);
+                self.walk_expression(right);
+            }
+            ExpressionKind::Member { object, .. } | ExpressionKind::Generic { object, .. } => {
+                self.walk_expression(object)
+            }
+            ExpressionKind::Index { object, indices } => {
+                self.walk_expression(object);
+                self.walk_expressions(indices);
+            }
+            ExpressionKind::Call { callee, args } => {
+                self.walk_expression(callee);
+                self.walk_expressions(args);
+            }
+            ExpressionKind::Dereference(object) => self.walk_expression(object),
+            ExpressionKind::Inherited(call) => {
+                if let Some(call) = call {
+                    self.walk_expression(call);
+                }
+            }
+            ExpressionKind::AnonymousMethod(method) => {
+                self.walk_implementation(&method.declarations);
+                self.walk_statements(&method.body);
+            }
+            ExpressionKind::Identifier(_)
+            | ExpressionKind::Number(_)
+            | ExpressionKind::String(_)
+            | ExpressionKind::Nil => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rule::*;
+
+    /// Reports every call of `Writeln`
+    struct NoWriteln;
+
+    impl Rule for NoWriteln {
+        fn id(&self) -> &'static str {
+            "no-writeln"
+        }
+
+        fn description(&self) -> &'static str {
+            "Writeln should not be used"
+        }
+
+        fn check_token(&self, tok: &Token, ctx: &mut Context) {
+            if tok.content.eq_ignore_ascii_case("writeln") {
+                ctx.report_token(tok, String::from("Writeln found"));
+            }
+        }
+    }
+
+    /// Reports every call statement at the start of the file
+    struct CountCalls;
+
+    impl Rule for CountCalls {
+        fn id(&self) -> &'static str {
+            "count-calls"
+        }
+
+        fn description(&self) -> &'static str {
+            "Reports every call statement"
+        }
+
+        fn default_severity(&self) -> Severity {
+            Severity::Info
+        }
+
+        fn check_statement(&self, statement: &Statement, ctx: &mut Context) {
+            if let StatementKind::Call(_) = statement.kind {
+                ctx.report_span(statement.span, String::from("call"));
+            }
+        }
+    }
+
+    const CONTENT: &str = "unit Foo;
+interface
+implementation
+procedure Bar;
+begin
+  Writeln('a');
+  if True then
+    Writeln('b');
+end;
+end.";
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(Box::new(NoWriteln));
+        registry.register(Box::new(CountCalls));
+        registry
+    }
+
+    #[test]
+    fn collects_diagnostics() {
+        let diagnostics = registry().check(CONTENT);
+
+        let writeln: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|d| d.code == "no-writeln")
+            .collect();
+        assert_eq!(writeln.len(), 2);
+        assert_eq!((writeln[0].row, writeln[0].col), (6, 3));
+        assert_eq!((writeln[1].row, writeln[1].col), (8, 5));
+        assert_eq!(writeln[1].end_col, 12);
+        assert_eq!(writeln[1].severity, Severity::Warning);
+
+        let calls: Vec<&Diagnostic> = diagnostics
+            .iter()
+            .filter(|d| d.code == "count-calls")
+            .collect();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].severity, Severity::Info);
+        assert_eq!((calls[0].row, calls[0].col), (6, 3));
+        assert_eq!((calls[0].end_row, calls[0].end_col), (6, 15));
+        assert_eq!((calls[1].row, calls[1].col), (8, 5));
+    }
+
+    #[test]
+    fn disabled_rules_and_severity() {
+        let mut registry = registry();
+        assert!(registry.set_enabled("count-calls", false));
+        assert!(registry.set_severity("no-writeln", Severity::Error));
+        assert!(!registry.set_enabled("unknown", false));
+
+        let diagnostics = registry.check(CONTENT);
+
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Error));
+        assert!(!registry.is_enabled("count-calls"));
+    }
+
+    #[test]
+    fn register_replaces_rule() {
+        let mut registry = registry();
+        registry.register(Box::new(NoWriteln));
+
+        assert_eq!(registry.rules().count(), 2);
+        assert_eq!(
+            registry.get("no-writeln").unwrap().description(),
+            "Writeln should not be used"
+        );
+    }
+
+    #[test]
+    fn tokens_only() {
+        let diagnostics = registry().check_tokens("Writeln('a'); ?");
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].code, "no-writeln");
+        assert_eq!(diagnostics[1].code, "syntax-error");
+    }
+
+    #[test]
+    fn configurations() {
+        let mut registry = registry();
+        registry.set_configurations(vec![
+            (String::from("Win32"), Defines::new(&["MSWINDOWS"])),
+            (String::from("Linux64"), Defines::new(&["LINUX"])),
+        ]);
+
+        let diagnostics = registry.check(
+            "unit Foo;
+interface
+implementation
+procedure Bar;
+begin
+  Baz;
+  {$IFDEF MSWINDOWS}
+  Writeln('win');
+  {$ELSE}
+  Writeln('other');
+  {$ENDIF}
+  {$IFDEF LINUX}
+  A(;
+  {$ENDIF}
+end;
+end.",
+        );
+
+        let found: Vec<(&str, Vec<String>)> = diagnostics
+            .iter()
+            .filter(|d| d.code != "no-writeln")
+            .map(|d| (d.code.as_str(), d.configurations.clone()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "count-calls",
+                    vec![String::from("Win32"), String::from("Linux64")]
+                ),
+                ("count-calls", vec![String::from("Win32")]),
+                ("count-calls", vec![String::from("Linux64")]),
+                ("syntax-error", vec![String::from("Linux64")]),
+            ]
+        );
+        // Token rules see the tokens of every branch
+        let writeln = diagnostics.iter().filter(|d| d.code == "no-writeln");
+        assert!(writeln.map(|d| d.configurations.len()).all(|len| len == 2));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let diagnostics = registry().check("unit Foo;\nimplementation\nend.");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "syntax-error");
+    }
+}
@@ -0,0 +1,426 @@
+use toml::Table;
+
+use crate::ast::*;
+use crate::config::{option_string, option_usize, unknown_option};
+use crate::diagnostic::Severity;
+use crate::lexer::{Token, TokenTyp};
+use crate::rule::{Context, Rule};
+use crate::suppression::UNUSED_SUPPRESSION;
+
+/// Every rule that ships with the linter
+pub fn builtin_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(TodoComment),
+        Box::new(TypePrefix::default()),
+        Box::new(EmptyMethod),
+        Box::new(MethodLength::default()),
+        Box::new(UnusedSuppression),
+    ]
+}
+
+/// Comments containing `TODO` or `FIXME`
+pub struct TodoComment;
+
+impl Rule for TodoComment {
+    fn id(&self) -> &'static str {
+        "todo-comment"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reports TODO and FIXME comments"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Info
+    }
+
+    fn check_token(&self, tok: &Token, ctx: &mut Context) {
+        if !matches!(tok.typ, TokenTyp::Comment | TokenTyp::DocComment) {
+            return;
+        }
+
+        if let Some(marker) = ["TODO", "FIXME"].iter().find(|m| tok.content.contains(*m)) {
+            ctx.report_token(tok, format!("{marker} comment"));
+        }
+    }
+}
+
+/// Type names start with `T`, interfaces with `I`, exceptions with `E` and pointers with `P`
+pub struct TypePrefix {
+    /// Prefix of classes, records and all other types
+    pub class: String,
+    pub interface: String,
+    pub exception: String,
+    /// Prefix of pointer types like `PFoo = ^TFoo`
+    pub pointer: String,
+}
+
+impl Default for TypePrefix {
+    fn default() -> Self {
+        TypePrefix {
+            class: String::from("T"),
+            interface: String::from("I"),
+            exception: String::from("E"),
+            pointer: String::from("P"),
+        }
+    }
+}
+
+impl Rule for TypePrefix {
+    fn id(&self) -> &'static str {
+        "type-prefix"
+    }
+
+    fn description(&self) -> &'static str {
+        "Type names start with T, interfaces with I, exceptions with E and pointers with P"
+    }
+
+    fn configure(&mut self, options: &Table) -> Result<(), String> {
+        for (key, value) in options {
+            match key.as_str() {
+                "class" => self.class = option_string(key, value)?,
+                "interface" => self.interface = option_string(key, value)?,
+                "exception" => self.exception = option_string(key, value)?,
+                "pointer" => self.pointer = option_string(key, value)?,
+                _ => return Err(unknown_option(key)),
+            }
+        }
+        Ok(())
+    }
+
+    fn check_type_declaration(&self, decl: &TypeDeclaration, ctx: &mut Context) {
+        let prefix = match &decl.definition {
+            TypeDefinition::Forward(_) => return,
+            TypeDefinition::Class(class) => match class.kind {
+                ClassKind::Interface | ClassKind::DispInterface => &self.interface,
+                ClassKind::Class if self.is_exception(class) => &self.exception,
+                _ => &self.class,
+            },
+            TypeDefinition::Alias(Type::Pointer(_)) => &self.pointer,
+            TypeDefinition::Alias(_) => &self.class,
+        };
+
+        if !decl.name.starts_with(prefix.as_str()) {
+            ctx.report(
+                format!("Type `{}` should start with `{prefix}`", decl.name),
+                decl.row,
+                decl.col,
+            );
+        }
+    }
+}
+
+impl TypePrefix {
+    /// Descendants of `Exception` or of a class with the exception prefix
+    fn is_exception(&self, class: &Class) -> bool {
+        class.ancestors.first().is_some_and(|parent| {
+            parent.eq_ignore_ascii_case("Exception") || parent.starts_with(self.exception.as_str())
+        })
+    }
+}
+
+/// Methods with an empty `begin end` body
+pub struct EmptyMethod;
+
+impl Rule for EmptyMethod {
+    fn id(&self) -> &'static str {
+        "empty-method"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reports methods without any statements"
+    }
+
+    fn check_method(&self, method: &Method, ctx: &mut Context) {
+        if method.body.as_ref().is_some_and(|body| body.is_empty()) {
+            ctx.report(
+                format!("Method `{}` is empty", method.head.name),
+                method.head.row,
+                method.head.col,
+            );
+        }
+    }
+}
+
+/// Suppression comments that suppress nothing, reported by the `Registry` itself
+/// after the suppressions are applied
+pub struct UnusedSuppression;
+
+impl Rule for UnusedSuppression {
+    fn id(&self) -> &'static str {
+        UNUSED_SUPPRESSION
+    }
+
+    fn description(&self) -> &'static str {
+        "Reports suppression comments that are malformed or suppress nothing"
+    }
+}
+
+/// Methods with more statements than `max_statements`, nested statements are counted too
+pub struct MethodLength {
+    pub max_statements: usize,
+}
+
+impl Default for MethodLength {
+    fn default() -> Self {
+        MethodLength { max_statements: 50 }
+    }
+}
+
+impl Rule for MethodLength {
+    fn id(&self) -> &'static str {
+        "method-length"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reports methods with too many statements"
+    }
+
+    fn configure(&mut self, options: &Table) -> Result<(), String> {
+        for (key, value) in options {
+            match key.as_str() {
+                "max_statements" => self.max_statements = option_usize(key, value)?,
+                _ => return Err(unknown_option(key)),
+            }
+        }
+        Ok(())
+    }
+
+    fn check_method(&self, method: &Method, ctx: &mut Context) {
+        let Some(body) = &method.body else {
+            return;
+        };
+
+        let count = count_statements(body);
+        if count > self.max_statements {
+            ctx.report(
+                format!(
+                    "Method `{}` has {count} statements, the maximum is {}",
+                    method.head.name, self.max_statements
+                ),
+                method.head.row,
+                method.head.col,
+            );
+        }
+    }
+}
+
+/// Counts the statements including the nested ones, compound statements themselves are not counted
+fn count_statements(statements: &[Statement]) -> usize {
+    statements.iter().map(count_statement).sum()
+}
+
+fn count_statement(statement: &Statement) -> usize {
+    let optional =
+        |statement: &Option<Box<Statement>>| statement.as_deref().map_or(0, count_statement);
+
+    match &statement.kind {
+        StatementKind::Compound(body) => count_statements(body),
+        StatementKind::If {
+            then_branch,
+            else_branch,
+            ..
+        } => 1 + optional(then_branch) + optional(else_branch),
+        StatementKind::Case {
+            branches,
+            else_branch,
+            ..
+        } => {
+            let branches: usize = branches
+                .iter()
+                .filter_map(|branch| branch.body.as_ref())
+                .map(count_statement)
+                .sum();
+            1 + branches + else_branch.as_deref().map_or(0, count_statements)
+        }
+        StatementKind::For { body, .. }
+        | StatementKind::ForIn { body, .. }
+        | StatementKind::While { body, .. }
+        | StatementKind::With { body, .. } => 1 + optional(body),
+        StatementKind::Labeled { statement, .. } => optional(statement),
+        StatementKind::Repeat { body, .. } => 1 + count_statements(body),
+        StatementKind::Try { body, handler } => {
+            let handler = match handler {
+                TryHandler::Except(block) => {
+                    let handlers: usize = block
+                        .handlers
+                        .iter()
+                        .filter_map(|handler| handler.body.as_ref())
+                        .map(count_statement)
+                        .sum();
+                    handlers + count_statements(&block.statements)
+                }
+                TryHandler::Finally(statements) => count_statements(statements),
+            };
+            1 + count_statements(body) + handler
+        }
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::diagnostic::Diagnostic;
+    use crate::rule::Registry;
+    use crate::rules::*;
+
+    fn check(rule: Box<dyn Rule>, content: &str) -> Vec<Diagnostic> {
+        let mut registry = Registry::new();
+        registry.register(rule);
+        registry.check(content)
+    }
+
+    #[test]
+    fn todo_comment() {
+        let diagnostics = check(
+            Box::new(TodoComment),
+            "unit Foo;\ninterface\n// TODO: remove\n{ FIXME }\n{ todo }\nimplementation\nend.",
+        );
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "TODO comment");
+        assert_eq!((diagnostics[1].row, diagnostics[1].col), (4, 1));
+        assert_eq!(diagnostics[1].severity, Severity::Info);
+    }
+
+    #[test]
+    fn type_prefix() {
+        let diagnostics = check(
+            Box::new(TypePrefix::default()),
+            "unit Foo;
+interface
+type
+  TFoo = class;
+  Foo = class end;
+  IBar = interface end;
+  Bar = interface end;
+  EError = class(Exception) end;
+  Failure = class(EBase) end;
+  TInt = Integer;
+  Int = Integer;
+  PFoo = ^TFoo;
+  TPInt = ^Integer;
+  PInts = array of PInteger;
+implementation
+end.",
+        );
+
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Type `Foo` should start with `T`",
+                "Type `Bar` should start with `I`",
+                "Type `Failure` should start with `E`",
+                "Type `Int` should start with `T`",
+                "Type `TPInt` should start with `P`",
+                "Type `PInts` should start with `T`",
+            ]
+        );
+        assert_eq!((diagnostics[0].row, diagnostics[0].col), (5, 3));
+    }
+
+    #[test]
+    fn type_prefix_options() {
+        let mut rule = TypePrefix::default();
+        let options = Config::parse(
+            "[rules.type-prefix]\nclass = \"C\"\nexception = \"Err\"\npointer = \"Ptr\"",
+        )
+        .unwrap()
+        .rules
+        .remove("type-prefix")
+        .unwrap()
+        .options;
+        rule.configure(&options).unwrap();
+
+        let diagnostics = check(
+            Box::new(rule),
+            "unit Foo;
+interface
+type
+  CFoo = class end;
+  TFoo = class end;
+  ErrFoo = class(Exception) end;
+  ErrBar = class(ErrFoo) end;
+  EBaz = class(ErrFoo) end;
+  PtrFoo = ^CFoo;
+  PFoo = ^CFoo;
+implementation
+end.",
+        );
+
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Type `TFoo` should start with `C`",
+                "Type `EBaz` should start with `Err`",
+                "Type `PFoo` should start with `Ptr`",
+            ]
+        );
+    }
+
+    #[test]
+    fn method_length() {
+        let mut rule = MethodLength::default();
+        let mut options = Table::new();
+        options.insert(String::from("max_statements"), toml::Value::Integer(3));
+        rule.configure(&options).unwrap();
+
+        let diagnostics = check(
+            Box::new(rule),
+            "unit Foo;
+interface
+implementation
+procedure Short;
+begin
+  A;
+  if B then
+  begin
+    C;
+  end;
+end;
+procedure Long;
+begin
+  A;
+  while B do
+    try
+      C;
+    finally
+      D;
+    end;
+end;
+end.",
+        );
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Method `Long` has 5 statements, the maximum is 3"
+        );
+    }
+
+    #[test]
+    fn empty_method() {
+        let diagnostics = check(
+            Box::new(EmptyMethod),
+            "unit Foo;
+interface
+implementation
+procedure Foo; forward;
+procedure TFoo.Bar;
+begin
+end;
+procedure Baz;
+begin
+  Bar;
+end;
+end.",
+        );
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Method `TFoo.Bar` is empty");
+        assert_eq!((diagnostics[0].row, diagnostics[0].col), (5, 11));
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use encoding_rs::{Encoding, UTF_8, UTF_16BE, UTF_16LE};
+
+/// Code page of files without BOM that are not valid UTF-8, Delphi's default on western Windows
+pub const DEFAULT_CODE_PAGE: u16 = 1252;
+
+/// How a source file is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceEncoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// A legacy code page like `1252`
+    Ansi(u16),
+}
+
+impl fmt::Display for SourceEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SourceEncoding::Utf8 => f.pad("UTF-8"),
+            SourceEncoding::Utf8Bom => f.pad("UTF-8 with BOM"),
+            SourceEncoding::Utf16Le => f.pad("UTF-16 LE"),
+            SourceEncoding::Utf16Be => f.pad("UTF-16 BE"),
+            SourceEncoding::Ansi(code_page) => f.pad(&format!("ANSI {code_page}")),
+        }
+    }
+}
+
+/// A decoded source file. The BOM is not part of `text`, so rows and columns count the
+/// characters as the editor shows them. Byte offsets of spans refer to `text`, not to the file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub text: String,
+    pub encoding: SourceEncoding,
+}
+
+impl SourceFile {
+    pub fn load(path: &Path, code_page: u16) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        SourceFile::decode(&bytes, code_page).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    /// Detects the encoding by the BOM. Files without BOM are UTF-8 if they are valid UTF-8,
+    /// otherwise they are decoded with `code_page`
+    pub fn decode(bytes: &[u8], code_page: u16) -> Result<Self, String> {
+        if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+            let encoding_kind = if encoding == UTF_8 {
+                SourceEncoding::Utf8Bom
+            } else if encoding == UTF_16LE {
+                SourceEncoding::Utf16Le
+            } else {
+                SourceEncoding::Utf16Be
+            };
+            return Ok(SourceFile {
+                text: decode_with(encoding, &bytes[bom_len..]),
+                encoding: encoding_kind,
+            });
+        }
+
+        if let Ok(text) = std::str::from_utf8(bytes) {
+            return Ok(SourceFile {
+                text: String::from(text),
+                encoding: SourceEncoding::Utf8,
+            });
+        }
+
+        let encoding = code_page_encoding(code_page)?;
+        let encoding_kind = if encoding == UTF_16LE {
+            SourceEncoding::Utf16Le
+        } else if encoding == UTF_16BE {
+            SourceEncoding::Utf16Be
+        } else {
+            SourceEncoding::Ansi(code_page)
+        };
+        Ok(SourceFile {
+            text: decode_with(encoding, bytes),
+            encoding: encoding_kind,
+        })
+    }
+}
+
+/// The encoding of a Windows code page number like `1252` or `932`
+pub fn code_page_encoding(code_page: u16) -> Result<&'static Encoding, String> {
+    codepage::to_encoding_no_replacement(code_page)
+        .ok_or_else(|| format!("Unknown code page `{code_page}`"))
+}
+
+/// Invalid sequences become U+FFFD, so every undecodable byte still takes one column
+fn decode_with(encoding: &'static Encoding, bytes: &[u8]) -> String {
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::*;
+
+    #[test]
+    fn boms() {
+        let file = SourceFile::decode(b"\xEF\xBB\xBFunit \xC3\xA4;", DEFAULT_CODE_PAGE).unwrap();
+        assert_eq!(file.text, "unit ä;");
+        assert_eq!(file.encoding, SourceEncoding::Utf8Bom);
+
+        let file = SourceFile::decode(b"\xFF\xFEu\x00\xE4\x00\r\x00\n\x00", 1252).unwrap();
+        assert_eq!(file.text, "uä\r\n");
+        assert_eq!(file.encoding, SourceEncoding::Utf16Le);
+
+        let file = SourceFile::decode(b"\xFE\xFF\x00u\xD8\x3D\xDE\x00", 1252).unwrap();
+        assert_eq!(file.text, "u😀");
+        assert_eq!(file.encoding, SourceEncoding::Utf16Be);
+    }
+
+    #[test]
+    fn without_bom() {
+        let file = SourceFile::decode("x := 'Größe';".as_bytes(), 1252).unwrap();
+        assert_eq!(file.text, "x := 'Größe';");
+        assert_eq!(file.encoding, SourceEncoding::Utf8);
+
+        // Not valid UTF-8, so the code page is used
+        let file = SourceFile::decode(b"x := 'Gr\xF6\xDFe \x80';", 1252).unwrap();
+        assert_eq!(file.text, "x := 'Größe €';");
+        assert_eq!(file.encoding, SourceEncoding::Ansi(1252));
+
+        let file = SourceFile::decode(b"'\xC4\xE0'", 1251).unwrap();
+        assert_eq!(file.text, "'Да'");
+        assert_eq!(file.encoding.to_string(), "ANSI 1251");
+
+        assert_eq!(
+            SourceFile::decode(b"\xFF", 1),
+            Err(String::from("Unknown code page `1`"))
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{Token, TokenTyp};
+
+/// Prefix of the comments that control the linter
+const PREFIX: &str = "delphi_linter:";
+
+/// Id of the diagnostics about suppressions that are malformed or never used
+pub const UNUSED_SUPPRESSION: &str = "unused-suppression";
+
+/// Lines in which diagnostics of some rules are dropped, created by comments like
+/// `{ delphi_linter: disable type-prefix }` until `{ delphi_linter: enable type-prefix }`
+/// or `// delphi_linter: ignore-next-line empty-method`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suppression {
+    /// An empty list suppresses every rule
+    pub rules: Vec<String>,
+    pub start_row: usize,
+    /// Inclusive, `usize::MAX` if the rules are never enabled again
+    pub end_row: usize,
+    /// Position of the comment
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Suppression {
+    fn matches(&self, d: &Diagnostic) -> bool {
+        (self.start_row..=self.end_row).contains(&d.row)
+            && (self.rules.is_empty() || self.rules.contains(&d.code))
+    }
+}
+
+/// Collects the suppressions of the comments in `tokens`, malformed comments are reported
+/// as warnings with the code `unused-suppression`
+pub fn parse_suppressions(tokens: &[Token]) -> (Vec<Suppression>, Vec<Diagnostic>) {
+    let mut res = Vec::new();
+    let mut diagnostics = Vec::new();
+    // Indices into `res` of the suppressions that are not enabled again yet
+    let mut open: Vec<usize> = Vec::new();
+
+    for tok in tokens
+        .iter()
+        .filter(|tok| matches!(tok.typ, TokenTyp::Comment | TokenTyp::DocComment))
+    {
+        let Some(directive) = strip_prefix(tok.content.trim()) else {
+            continue;
+        };
+
+        let mut words = directive.split([' ', ',', '\t']).filter(|w| !w.is_empty());
+        let command = words.next().unwrap_or_default();
+        let rules: Vec<String> = words.map(String::from).collect();
+
+        let suppression = Suppression {
+            rules: rules.clone(),
+            start_row: tok.span.start.row,
+            end_row: usize::MAX,
+            row: tok.span.start.row,
+            col: tok.span.start.col,
+        };
+
+        match command {
+            "disable" => {
+                open.push(res.len());
+                res.push(suppression);
+            }
+            "ignore-next-line" => res.push(Suppression {
+                start_row: tok.span.end.row + 1,
+                end_row: tok.span.end.row + 1,
+                ..suppression
+            }),
+            "enable" => {
+                // `enable` without rules ends every open block, otherwise the blocks with the same rules
+                let before = open.len();
+                open.retain(|&i| {
+                    let is_closed = rules.is_empty() || res[i].rules == rules;
+                    if is_closed {
+                        res[i].end_row = tok.span.start.row;
+                    }
+                    !is_closed
+                });
+                if open.len() == before {
+                    diagnostics.push(suppression_error(
+                        tok,
+                        "`enable` without matching `disable`",
+                    ));
+                }
+            }
+            _ => diagnostics.push(suppression_error(
+                tok,
+                &format!(
+                    "Unknown command `{command}`, expected disable, enable or ignore-next-line"
+                ),
+            )),
+        }
+    }
+
+    (res, diagnostics)
+}
+
+fn strip_prefix(content: &str) -> Option<&str> {
+    let prefix = content.get(..PREFIX.len())?;
+    if prefix.eq_ignore_ascii_case(PREFIX) {
+        Some(&content[PREFIX.len()..])
+    } else {
+        None
+    }
+}
+
+fn suppression_error(tok: &Token, message: &str) -> Diagnostic {
+    Diagnostic::with_span(
+        Severity::Warning,
+        UNUSED_SUPPRESSION,
+        format!("Invalid suppression: {message}"),
+        tok.span,
+    )
+}
+
+/// Drops the suppressed diagnostics. Rules of a suppression that suppressed nothing are
+/// reported with `unused`, unless `is_enabled` returns `Some(false)` for them. Ids for which
+/// it returns `None` are reported as unknown. Pass `None` to not report unused suppressions
+/// at all
+pub fn apply_suppressions(
+    suppressions: &[Suppression],
+    diagnostics: Vec<Diagnostic>,
+    unused: Option<Severity>,
+    is_enabled: impl Fn(&str) -> Option<bool>,
+) -> Vec<Diagnostic> {
+    // Which rules of every suppression were used, `true` at index 0 for suppressions of all rules
+    let mut used: Vec<Vec<bool>> = suppressions
+        .iter()
+        .map(|s| vec![false; s.rules.len().max(1)])
+        .collect();
+
+    let mut res: Vec<Diagnostic> = diagnostics
+        .into_iter()
+        .filter(|d| {
+            let mut is_suppressed = false;
+            for (suppression, used) in suppressions.iter().zip(used.iter_mut()) {
+                if suppression.matches(d) {
+                    let index = suppression
+                        .rules
+                        .iter()
+                        .position(|r| *r == d.code)
+                        .unwrap_or(0);
+                    used[index] = true;
+                    is_suppressed = true;
+                }
+            }
+            !is_suppressed
+        })
+        .collect();
+
+    let Some(severity) = unused else {
+        return res;
+    };
+
+    for (suppression, used) in suppressions.iter().zip(used) {
+        for (rule, _) in suppression
+            .rules
+            .iter()
+            .zip(&used)
+            .filter(|(rule, used)| !**used && is_enabled(rule).is_none())
+        {
+            res.push(Diagnostic::new(
+                severity,
+                UNUSED_SUPPRESSION,
+                format!("Unknown rule `{rule}` in suppression"),
+                suppression.row,
+                suppression.col,
+            ));
+        }
+
+        let message = if suppression.rules.is_empty() {
+            if used[0] {
+                continue;
+            }
+            String::from("Unused suppression")
+        } else {
+            let unused: Vec<String> = suppression
+                .rules
+                .iter()
+                .zip(&used)
+                .filter(|(rule, used)| !**used && is_enabled(rule) == Some(true))
+                .map(|(rule, _)| format!("`{rule}`"))
+                .collect();
+            if unused.is_empty() {
+                continue;
+            }
+            format!("Unused suppression of {}", unused.join(", "))
+        };
+
+        res.push(Diagnostic::new(
+            severity,
+            UNUSED_SUPPRESSION,
+            message,
+            suppression.row,
+            suppression.col,
+        ));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rule::Registry;
+    use crate::suppression::*;
+
+    fn lex(content: &str) -> Vec<Token<'_>> {
+        crate::lexer::Lexer::new(content).collect()
+    }
+
+    #[test]
+    fn parse() {
+        let (suppressions, diagnostics) =
+            parse_suppressions(&lex("{ delphi_linter: disable a, b }
+// delphi_linter: ignore-next-line c
+x
+{ DELPHI_LINTER: enable a, b }
+{ delphi_linter: disable }
+{ just a comment }"));
+
+        assert_eq!(diagnostics, vec![]);
+        assert_eq!(
+            suppressions,
+            vec![
+                Suppression {
+                    rules: vec![String::from("a"), String::from("b")],
+                    start_row: 1,
+                    end_row: 4,
+                    row: 1,
+                    col: 1,
+                },
+                Suppression {
+                    rules: vec![String::from("c")],
+                    start_row: 3,
+                    end_row: 3,
+                    row: 2,
+                    col: 1,
+                },
+                Suppression {
+                    rules: vec![],
+                    start_row: 5,
+                    end_row: usize::MAX,
+                    row: 5,
+                    col: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_comments() {
+        let (_, diagnostics) = parse_suppressions(&lex(
+            "{ delphi_linter: enable a }\n// delphi_linter: disabel a",
+        ));
+
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Invalid suppression: `enable` without matching `disable`",
+                "Invalid suppression: Unknown command `disabel`, expected disable, enable or ignore-next-line",
+            ]
+        );
+    }
+
+    const CONTENT: &str = "unit Foo;
+interface
+type
+  // delphi_linter: ignore-next-line type-prefix
+  Foo = Integer;
+  // delphi_linter: ignore-next-line type-prefix, empty-method
+  Bar = Integer;
+  { delphi_linter: disable type-prefix }
+  A = Integer;
+  B = Integer;
+  { delphi_linter: enable type-prefix }
+  C = Integer;
+  { delphi_linter: disable }
+implementation
+// TODO later
+end.";
+
+    #[test]
+    fn drops_suppressed_diagnostics() {
+        let diagnostics = Registry::with_builtin_rules().check(CONTENT);
+
+        let found: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.row, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (6, "Unused suppression of `empty-method`"),
+                (12, "Type `C` should start with `T`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_suppressions_of_disabled_rules() {
+        let mut registry = Registry::with_builtin_rules();
+        registry.set_enabled("empty-method", false);
+        registry.set_enabled("todo-comment", false);
+
+        let diagnostics = registry.check(CONTENT);
+
+        let found: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.row, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (12, "Type `C` should start with `T`"),
+                (13, "Unused suppression"),
+            ]
+        );
+
+        registry.set_enabled(UNUSED_SUPPRESSION, false);
+        assert_eq!(registry.check(CONTENT).len(), 1);
+    }
+
+    #[test]
+    fn unknown_rules() {
+        let diagnostics = Registry::with_builtin_rules().check(
+            "unit Foo;
+interface
+{ delphi_linter: disable Tpyo, type-prefix }
+type
+  A = Integer;
+{ delphi_linter: enable }
+// delphi_linter: ignore-next-line syntax-error
+implementation
+end.",
+        );
+
+        let found: Vec<(usize, &str)> = diagnostics
+            .iter()
+            .map(|d| (d.row, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (3, "Unknown rule `Tpyo` in suppression"),
+                (7, "Unused suppression of `syntax-error`"),
+            ]
+        );
+    }
+}